@@ -133,6 +133,44 @@ impl Instance {
         }
     }
 
+    // Device without a surface for compute only work, the presentation queue is the graphic one
+    pub unsafe fn create_device_headless(&self, physical_device: &crate::PhysicalDevice, features: ash::vk::PhysicalDeviceFeatures, extensions: Vec<&str>) -> crate::Device {
+        let extensions: Vec<CString> = extensions.iter().map(|extension_str| CString::new(*extension_str).unwrap()).collect();
+        let extensions_raw: Vec<*const i8> = extensions.iter().map(|extension_str| extension_str.as_bytes_with_nul() as *const [u8] as *const i8).collect();
+
+        let graphic_queue = physical_device.get_graphic_queue_family_index().or(physical_device.get_compute_queue_family_index()).unwrap();
+        let compute_queue = physical_device.get_compute_queue_family_index().unwrap();
+
+        let mut queue_create_infos = vec![ash::vk::DeviceQueueCreateInfo {
+            queue_family_index: graphic_queue.family_index,
+            queue_count: 1,
+            p_queue_priorities: &1.0,
+            ..Default::default()
+        }];
+        if compute_queue.family_index != graphic_queue.family_index {
+            queue_create_infos.push(ash::vk::DeviceQueueCreateInfo {
+                queue_family_index: compute_queue.family_index,
+                queue_count: 1,
+                p_queue_priorities: &1.0,
+                ..Default::default()
+            });
+        }
+
+        let device_create_info = ash::vk::DeviceCreateInfo::builder()
+            .enabled_extension_names(&extensions_raw)
+            .enabled_features(&features)
+            .queue_create_infos(&queue_create_infos);
+
+        let device_raw = self.raw.create_device(physical_device.raw, &device_create_info, None).unwrap();
+
+        crate::Device {
+            raw: device_raw,
+            presentation_queue: crate::QueueFamily { family_index: graphic_queue.family_index, count: graphic_queue.count },
+            graphic_queue,
+            compute_queue,
+        }
+    }
+
     pub unsafe fn create_surface(&self, window: &dyn raw_window_handle::HasRawWindowHandle) -> crate::Surface {
         let surface_khr = ash_window::create_surface(&self.entry, &self.raw, window, None).unwrap();
 
@@ -17,6 +17,17 @@ impl PhysicalDevice {
         self.device_type() == ash::vk::PhysicalDeviceType::DISCRETE_GPU
     }
 
+    // Lower is better: discrete, integrated, virtual then software devices such as lavapipe
+    pub fn preference(&self) -> u32 {
+        match self.device_type() {
+            ash::vk::PhysicalDeviceType::DISCRETE_GPU => { 0 }
+            ash::vk::PhysicalDeviceType::INTEGRATED_GPU => { 1 }
+            ash::vk::PhysicalDeviceType::VIRTUAL_GPU => { 2 }
+            ash::vk::PhysicalDeviceType::CPU => { 3 }
+            _ => { 4 }
+        }
+    }
+
     pub fn has_compute_queue(&self) -> bool {
         for queue_family in self.queue_family_properties.iter() {
             if queue_family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE) {
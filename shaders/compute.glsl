@@ -0,0 +1,265 @@
+#version 460
+
+layout (local_size_x = 256) in;
+
+struct Particle {
+    ivec2 position;
+    vec2 speed;
+    uint species;
+    float lifetime;
+    float mass;
+    float charge;
+};
+
+struct Vertex {
+    ivec2 position;
+    vec2 speed;
+    uint species;
+    float charge;
+};
+
+struct Source {
+    ivec2 position;
+    uint law;
+    float force;
+    float parameter;
+    float cutoff;
+    float max_acceleration;
+    float charge;
+    float charge_softening_squared;
+    uint void_1;
+};
+
+layout (std430, binding = 0) buffer Particles {
+    Particle particles[];
+};
+
+layout (std430, binding = 1) buffer Vertices {
+    Vertex vertices[];
+};
+
+layout (std430, binding = 2) readonly buffer Sources {
+    Source sources[];
+};
+
+layout (push_constant) uniform Parameters {
+    ivec2 size_field;
+    ivec2 camera;
+    float elapsed;
+    float max_speed;
+    float energy_loss;
+    float wall_restitution;
+    float wall_margin;
+    float wall_repulsion;
+    uint boundary_x;
+    uint boundary_y;
+    uint spawn_rule;
+    uint count;
+    uint num_sources;
+    uint seed;
+    uint pixel;
+    uint mode;
+} parameters;
+
+const uint MODE_STEP = 0u;
+const uint MODE_VERTICES = 1u;
+
+const uint LAW_LINEAR = 0u;
+const uint LAW_SQUARE = 1u;
+const uint LAW_SOFTENED = 2u;
+const uint LAW_INVERSE_CUBE = 3u;
+const uint LAW_SPRING = 4u;
+const uint LAW_GAUSSIAN = 5u;
+const uint LAW_VORTEX = 6u;
+
+const uint BOUNDARY_REFLECT = 0u;
+const uint BOUNDARY_WRAP = 1u;
+const uint BOUNDARY_ABSORB = 2u;
+const uint BOUNDARY_SOFT = 3u;
+
+const uint SPAWN_RANDOM = 0u;
+const uint SPAWN_CENTER = 1u;
+
+// PCG hash, the GPU stream differs from the CPU one but only matters for coincident points and respawns
+uint hash(uint value) {
+    uint state = value * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+int random_range(inout uint state, int low, int high) {
+    state = hash(state);
+    return low + int(state % uint(high - low));
+}
+
+// Rounds half away from zero like the CPU
+vec2 round_away(vec2 value) {
+    return sign(value) * floor(abs(value) + 0.5);
+}
+
+int wrap_direction(int direction, int size) {
+    int half_size = size / 2;
+    if (abs(direction) > half_size) {
+        return -sign(direction) * (half_size - abs(direction) % half_size);
+    }
+    return direction;
+}
+
+ivec2 direction_to(ivec2 position, ivec2 source, inout uint state) {
+    ivec2 direction = source - position;
+    if (position == source) {
+        direction.x = random_range(state, 1, 100) * (random_range(state, 0, 2) * 2 - 1);
+        direction.y = random_range(state, 1, 100) * (random_range(state, 0, 2) * 2 - 1);
+    }
+    if (parameters.boundary_x == BOUNDARY_WRAP) { direction.x = wrap_direction(direction.x, parameters.size_field.x); }
+    if (parameters.boundary_y == BOUNDARY_WRAP) { direction.y = wrap_direction(direction.y, parameters.size_field.y); }
+    return direction;
+}
+
+float magnitude(Source source, float distance, float distance_squared) {
+    if (source.law == LAW_SQUARE) { return source.force / distance_squared; }
+    if (source.law == LAW_SOFTENED) {
+        float inverse = 1.0 / sqrt(distance_squared + source.parameter);
+        return source.force * distance * inverse * inverse * inverse;
+    }
+    if (source.law == LAW_INVERSE_CUBE) { return source.force / (distance_squared * distance); }
+    if (source.law == LAW_SPRING) { return source.force * (distance - source.parameter); }
+    if (source.law == LAW_GAUSSIAN) { return source.force * distance * exp(-distance_squared / source.parameter); }
+    return source.force / distance;
+}
+
+vec2 acceleration(Particle particle, inout uint state) {
+    vec2 total = vec2(0.0, 0.0);
+    float charge_to_mass = 0.0;
+    if (particle.mass > 0.0) { charge_to_mass = particle.charge / particle.mass; }
+
+    for (uint i = 0u; i < parameters.num_sources; i++) {
+        Source source = sources[i];
+        vec2 direction = vec2(direction_to(particle.position, source.position, state));
+        float distance_squared = dot(direction, direction);
+        float distance = sqrt(distance_squared);
+        vec2 unit = direction / distance;
+
+        float force = clamp(magnitude(source, distance, distance_squared), -source.max_acceleration, source.max_acceleration);
+        if (distance > source.cutoff) { force = 0.0; }
+        if (source.law == LAW_VORTEX) {
+            total += vec2(-unit.y, unit.x) * force;
+        } else {
+            total += unit * force;
+        }
+
+        if (source.charge != 0.0) {
+            float charge = -source.charge * charge_to_mass / (distance_squared + source.charge_softening_squared);
+            total += unit * clamp(charge, -source.max_acceleration, source.max_acceleration);
+        }
+    }
+
+    float margin = parameters.wall_margin;
+    if (margin > 0.0) {
+        vec2 position = vec2(particle.position);
+        vec2 size = vec2(parameters.size_field);
+        vec2 near = max(1.0 - position / margin, 0.0);
+        vec2 far = max(1.0 - (size - position) / margin, 0.0);
+        vec2 repulsion = (near * near - far * far) * parameters.wall_repulsion;
+        if (parameters.boundary_x == BOUNDARY_SOFT) { total.x += repulsion.x; }
+        if (parameters.boundary_y == BOUNDARY_SOFT) { total.y += repulsion.y; }
+    }
+    return total;
+}
+
+// Returns true when the particle left through an absorbing side
+bool apply_boundary(inout int position, inout float speed, int size, uint boundary) {
+    if (boundary == BOUNDARY_REFLECT || boundary == BOUNDARY_SOFT) {
+        float restitution = 1.0;
+        if (boundary == BOUNDARY_SOFT) { restitution = parameters.wall_restitution; }
+        if (position <= 0) {
+            speed = abs(speed) * restitution;
+            position = 0;
+        }
+        if (position >= size) {
+            speed = abs(speed) * -restitution;
+            position = size;
+        }
+        return false;
+    }
+    if (boundary == BOUNDARY_WRAP) {
+        if (position < 0) { position += size * ((-position - 1) / size + 1); }
+        position = position % size;
+        return false;
+    }
+    return position < 0 || position > size;
+}
+
+void respawn(inout Particle particle, inout uint state) {
+    ivec2 size = parameters.size_field;
+    if (parameters.spawn_rule == SPAWN_RANDOM) {
+        particle.position = ivec2(random_range(state, 0, size.x), random_range(state, 0, size.y));
+        particle.speed = vec2(0.0, 0.0);
+    } else if (parameters.spawn_rule == SPAWN_CENTER) {
+        int pixel = int(parameters.pixel);
+        particle.position = size / 2 + ivec2(random_range(state, -pixel, pixel), random_range(state, -pixel, pixel));
+        particle.speed = vec2(0.0, 0.0);
+    } else if (particle.position.x < 0 || particle.position.x > size.x) {
+        if (particle.position.x < 0) { particle.position.x = size.x; } else { particle.position.x = 0; }
+        particle.position.y = random_range(state, 0, size.y);
+    } else {
+        if (particle.position.y < 0) { particle.position.y = size.y; } else { particle.position.y = 0; }
+        particle.position.x = random_range(state, 0, size.x);
+    }
+}
+
+// Semi-implicit Euler step mirroring Entity::apply_gravity and Entity::update_position
+void step(uint index) {
+    Particle particle = particles[index];
+    uint state = hash(index ^ hash(parameters.seed));
+    float elapsed = parameters.elapsed;
+
+    particle.speed += acceleration(particle, state) * elapsed;
+
+    float speed = length(particle.speed);
+    if (speed > parameters.max_speed) { particle.speed *= parameters.max_speed / speed; }
+    particle.speed *= max(1.0 - elapsed * parameters.energy_loss, 0.1);
+
+    particle.position += ivec2(round_away(particle.speed * elapsed));
+
+    int position_x = particle.position.x;
+    int position_y = particle.position.y;
+    float speed_x = particle.speed.x;
+    float speed_y = particle.speed.y;
+    bool absorbed_x = apply_boundary(position_x, speed_x, parameters.size_field.x, parameters.boundary_x);
+    bool absorbed_y = apply_boundary(position_y, speed_y, parameters.size_field.y, parameters.boundary_y);
+    particle.position = ivec2(position_x, position_y);
+    particle.speed = vec2(speed_x, speed_y);
+    if (absorbed_x || absorbed_y) { respawn(particle, state); }
+
+    particles[index] = particle;
+}
+
+// Integer subtraction wraps in GLSL, the overflow is detected from the sign of the origin and saturated
+int relative(int value, int origin) {
+    int difference = value - origin;
+    if (origin < 0 && difference < value) { return 2147483647; }
+    if (origin > 0 && difference > value) { return int(0x80000000u); }
+    return difference;
+}
+
+// Camera relative vertices for the particles pipeline, clamped like renderer_vulkan::relative_position
+void write_vertex(uint index) {
+    Particle particle = particles[index];
+    Vertex vertex;
+    vertex.position = ivec2(relative(particle.position.x, parameters.camera.x), relative(particle.position.y, parameters.camera.y));
+    vertex.speed = particle.speed;
+    vertex.species = particle.species;
+    vertex.charge = particle.charge;
+    vertices[index] = vertex;
+}
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= parameters.count) { return; }
+    if (parameters.mode == MODE_STEP) {
+        step(index);
+    } else {
+        write_vertex(index);
+    }
+}
\ No newline at end of file
@@ -1,18 +1,22 @@
 #version 460
 
 layout (location = 0) in vec2 speed;
+layout (location = 1) flat in uint species;
+layout (location = 2) in float charge;
 
 layout (location = 0) out vec4 fragColor;
 
 layout (binding = 0) uniform Uniform {
     ivec2 window;
-    ivec2 camera;
     float zoom;
     float alpha;
 
     vec3 color_base;
     vec3 color_fast;
     float color_ratio;
+    uint color_mode;
+
+    vec4 species_colors[8];
 } info;
 
 void main() {
@@ -24,5 +28,11 @@ void main() {
     float factor_speed = min(info.color_ratio * (speed_total / 100000.0), 1.0);
 
     vec3 color_final = mix(info.color_base, info.color_fast, factor_speed);
+    if (info.color_mode == 1) {
+        color_final = info.species_colors[min(species, 7u)].rgb;
+    } else if (info.color_mode == 2) {
+        vec3 color_charge = charge > 0.0 ? vec3(1.0, 0.2, 0.2) : vec3(0.2, 0.4, 1.0);
+        color_final = mix(vec3(0.5, 0.5, 0.5), color_charge, min(abs(charge), 1.0));
+    }
     fragColor = vec4(color_final, info.alpha);
 }
\ No newline at end of file
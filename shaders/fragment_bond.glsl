@@ -0,0 +1,7 @@
+#version 460
+
+layout (location = 0) out vec4 fragColor;
+
+void main() {
+    fragColor = vec4(0.9, 0.6, 0.3, 0.8);
+}
\ No newline at end of file
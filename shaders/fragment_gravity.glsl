@@ -2,9 +2,20 @@
 
 layout (location = 0) in vec2 speed;
 layout (location = 1) in float gravity;
+layout (location = 2) flat in uint law;
 
 layout (location = 0) out vec4 fragColor;
 
+const vec3 law_colors[7] = vec3[7](
+    vec3(1.0, 1.0, 1.0),
+    vec3(1.0, 0.5, 0.0),
+    vec3(0.0, 1.0, 0.5),
+    vec3(1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 1.0),
+    vec3(1.0, 0.0, 1.0),
+    vec3(0.5, 1.0, 0.0)
+);
+
 void main() {
     float speed_total = sqrt(speed.x * speed.x + speed.y * speed.y);
 
@@ -17,7 +28,13 @@ void main() {
 
     vec2 point_coord = (gl_PointCoord - vec2(0.5, 0.5)) * 2.0;
 
-    if ((point_coord.x * point_coord.x + point_coord.y * point_coord.y) > 1.0) {
+    float distance_center = point_coord.x * point_coord.x + point_coord.y * point_coord.y;
+
+    if (distance_center < 0.25) {
+        fragColor.xyz = law_colors[min(law, 6u)];
+    }
+
+    if (distance_center > 1.0) {
         fragColor.w = 0.0;
     } else {
         fragColor.w = 0.5;
@@ -2,28 +2,36 @@
 
 layout (location = 0) in ivec2 position;
 layout (location = 1) in vec2 speed_in;
+layout (location = 2) in uint species_in;
+layout (location = 3) in float charge_in;
 
 layout (location = 0) out vec2 speed;
+layout (location = 1) flat out uint species;
+layout (location = 2) out float charge;
 
 layout (binding = 0) uniform Uniform {
     ivec2 window;
-    ivec2 camera;
     float zoom;
     float alpha;
     
     vec3 color_base;
     vec3 color_fast;
     float color_ratio;
+    uint color_mode;
+
+    vec4 species_colors[8];
 } info;
 
 void main() {
-    ivec2 position_relative = position.xy - info.camera;
+    vec2 position_relative = vec2(position.xy);
 
     //gl_Position = vec4(float(position.x) / float(window.x), float(position.y) / float(window.y), 0.0, 1.0);
     //gl_Position.xy = gl_Position.xy / 10000.0;
 
-    gl_Position = vec4(float(position_relative.x) / float(info.window.x), float(position_relative.y) / float(-info.window.y), 0.0, 1.0);
+    gl_Position = vec4(position_relative.x / float(info.window.x), position_relative.y / float(-info.window.y), 0.0, 1.0);
     gl_Position.xy = gl_Position.xy / info.zoom;
 
     speed = speed_in;
+    species = species_in;
+    charge = charge_in;
 }
\ No newline at end of file
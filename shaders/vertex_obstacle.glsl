@@ -0,0 +1,23 @@
+#version 460
+
+layout (location = 0) in ivec2 position;
+
+layout (binding = 0) uniform Uniform {
+    ivec2 window;
+    float zoom;
+    float alpha;
+    
+    vec3 color_base;
+    vec3 color_fast;
+    float color_ratio;
+    uint color_mode;
+
+    vec4 species_colors[8];
+} info;
+
+void main() {
+    vec2 position_relative = vec2(position.xy);
+
+    gl_Position = vec4(position_relative.x / float(info.window.x), position_relative.y / float(-info.window.y), 0.0, 1.0);
+    gl_Position.xy = gl_Position.xy / info.zoom;
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use crate::Coordinate;
+use crate::entity::{GravityCompute, GravityEntity};
+use crate::world::World;
+
+use euclid::default::Vector2D;
+
+const STEPS: u32 = 10;
+const NUM_SOURCES: usize = 8;
+
+// Headless timing of the scalar and the SIMD step from 2^18 to 2^22 particles, run with --bench
+pub fn run(mut world: Box<World>) {
+    let threads = std::thread::available_parallelism().map_or(1, |num| num.get());
+    while world.threadpool.num_threads() < threads { world.threadpool.new_thread(); }
+    world.settings.diagnostics = false;
+    world.elapsed_since_update = 1.0 / 120.0;
+
+    println!("threads = {}, gravity sources = {}, steps = {}", world.threadpool.num_threads(), NUM_SOURCES, STEPS);
+    println!("{:>10} {:>12} {:>12} {:>8}", "particles", "scalar_ms", "simd_ms", "speedup");
+    for power in 18..=22 {
+        world.max_particles = 2i32.pow(power);
+        world.reset();
+        add_sources(&mut world);
+
+        let scalar = time_step(&mut world, false);
+        let simd = time_step(&mut world, true);
+        println!("{:>10} {:>12.2} {:>12.2} {:>7.2}x", world.entities.len(), scalar, simd, scalar / simd);
+    }
+}
+
+fn add_sources(world: &mut World) {
+    let center = euclid::default::Point2D::new(world.size_field.0 / 2, world.size_field.1 / 2);
+    let radius = world.size_field.0.min(world.size_field.1) as f32 / 4.0;
+    for i in 0..NUM_SOURCES {
+        let angle = i as f32 / NUM_SOURCES as f32 * std::f32::consts::TAU;
+        let mut gravity = GravityEntity::default();
+        gravity.inner.position = center + (Vector2D::new(angle.cos(), angle.sin()) * radius).round().cast::<Coordinate>();
+        gravity.gravity.compute = GravityCompute::Softened { force: 100.0, softening: 5.0 };
+        world.entities_gravity.push(gravity);
+    }
+}
+
+// Average milliseconds per step after one warm up step
+fn time_step(world: &mut World, simd: bool) -> f64 {
+    world.settings.simd = simd;
+    world.step();
+    let time = std::time::Instant::now();
+    for _ in 0..STEPS { world.step(); }
+    time.elapsed().as_secs_f64() * 1_000.0 / STEPS as f64
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use crate::PRECISION;
+use crate::entity::Entity;
+use crate::grid::Grid;
+use crate::threadpool::Threadpool;
+use crate::world::Settings;
+
+use euclid::default::Vector2D;
+
+pub fn update_accelerations(entities: &[Entity], grid: &Grid, settings: &Settings, accelerations: &mut Vec<Vector2D<f32>>, threadpool: &mut Threadpool) {
+    accelerations.resize(entities.len(), Vector2D::zero());
+    let precision = PRECISION as f32;
+    let radius = settings.perception_radius * precision;
+
+    threadpool.for_each_chunk_mut(accelerations, |_chunk, offset, accelerations| {
+        for (i, acceleration) in accelerations.iter_mut().enumerate() {
+            let index = offset + i;
+            let entity = &entities[index];
+
+            let mut separation = Vector2D::zero();
+            let mut speed_sum = Vector2D::zero();
+            let mut direction_sum = Vector2D::zero();
+            let mut num_neighbours = 0;
+            grid.for_each_neighbour(entities, entity.position, radius, |other, direction, distance_squared| {
+                if other == index { return }
+                let direction = direction / precision;
+                if distance_squared > 0.0 {
+                    separation -= direction / (distance_squared / (precision * precision));
+                }
+                speed_sum += entities[other].speed;
+                direction_sum += direction;
+                num_neighbours += 1;
+            });
+
+            let speed = entity.speed / precision;
+            let mut steering = Vector2D::zero();
+            if num_neighbours > 0 {
+                let num_neighbours = num_neighbours as f32;
+                steering += separation * settings.separation;
+                steering += (speed_sum / (num_neighbours * precision) - speed) * settings.alignment;
+                steering += direction_sum / num_neighbours * settings.cohesion;
+            }
+            if speed.square_length() > 0.0 {
+                steering += speed.normalize() * (settings.boids_speed - speed.length());
+            }
+
+            *acceleration = steering * precision;
+        }
+    });
+}
\ No newline at end of file
@@ -0,0 +1,210 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::Entity;
+use crate::world::{Settings, World};
+
+use euclid::default::{Point2D, Vector2D};
+
+#[derive(Clone, Copy)]
+pub struct Bond {
+    pub a: usize,
+    pub b: usize,
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shape {
+    Chain,
+    Grid,
+    Circle,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Chain, Shape::Grid, Shape::Circle];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Chain => { "chain" }
+            Shape::Grid => { "grid" }
+            Shape::Circle => { "circle" }
+        }
+    }
+}
+
+fn inverse_mass(entity: &Entity) -> f32 {
+    if entity.mass > 0.0 { 1.0 / entity.mass } else { 0.0 }
+}
+
+// Damped Hooke springs applied as speed kicks before the integration, returns the number of bonds broken by strain and the largest spring acceleration
+pub fn apply(entities: &mut [Entity], bonds: &mut Vec<Bond>, settings: &Settings, elapsed: f32, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) -> (usize, f32) {
+    let len = bonds.len();
+    let mut max_acceleration: f32 = 0.0;
+    bonds.retain(|bond| {
+        if bond.a >= entities.len() || bond.b >= entities.len() || bond.a == bond.b { return false }
+        let (a, b) = (&entities[bond.a], &entities[bond.b]);
+        let direction = Entity::direction_to(a.position, b.position, settings, size_field, rng).to_f32();
+        let distance = direction.length();
+        let rest_length = bond.rest_length * PRECISION as f32;
+        if settings.bond_break_strain > 0.0 && (distance - rest_length).abs() > settings.bond_break_strain * rest_length { return false }
+        if distance <= 0.0 { return true }
+
+        let normal = direction / distance;
+        let force = bond.stiffness * (distance - rest_length) + bond.damping * (b.speed - a.speed).dot(normal);
+        let (inverse_a, inverse_b) = (inverse_mass(a), inverse_mass(b));
+        max_acceleration = max_acceleration.max(force.abs() * inverse_a.max(inverse_b));
+        entities[bond.a].speed += normal * force * inverse_a * elapsed;
+        entities[bond.b].speed -= normal * force * inverse_b * elapsed;
+        true
+    });
+    (len - bonds.len(), max_acceleration)
+}
+
+// Follows a retain on the particles, kept holds one flag per particle before the retain
+pub fn remap(bonds: &mut Vec<Bond>, kept: &[bool]) {
+    let mut new_indices = Vec::with_capacity(kept.len());
+    let mut next = 0;
+    for keep in kept.iter() {
+        new_indices.push(next);
+        if *keep { next += 1; }
+    }
+    bonds.retain_mut(|bond| {
+        if !kept.get(bond.a).copied().unwrap_or(false) || !kept.get(bond.b).copied().unwrap_or(false) { return false }
+        bond.a = new_indices[bond.a];
+        bond.b = new_indices[bond.b];
+        true
+    });
+}
+
+pub fn truncate(bonds: &mut Vec<Bond>, len: usize) {
+    bonds.retain(|bond| bond.a < len && bond.b < len);
+}
+
+// Appends the particles and bonds of a shape around the center, the particle cap grows to keep them
+// The shape is shifted inside the field as a whole, a shape larger than the field isn't spawned
+pub fn spawn(world: &mut World, shape: Shape, center: Point2D<Coordinate>) {
+    let size = world.settings.bond_size.max(2) as usize;
+    let spacing = world.settings.bond_spacing * PRECISION as f32;
+    let mut offsets: Vec<Vector2D<f32>> = Vec::new();
+    let mut pairs = Vec::new();
+
+    match shape {
+        Shape::Chain => {
+            let offset = (size - 1) as f32 * spacing / 2.0;
+            for i in 0..size { offsets.push(Vector2D::new(i as f32 * spacing - offset, 0.0)); }
+            for i in 1..size { pairs.push((i - 1, i)); }
+        }
+        Shape::Grid => {
+            // Structural and shear springs, the diagonals keep the cells from collapsing
+            let offset = (size - 1) as f32 * spacing / 2.0;
+            for y in 0..size {
+                for x in 0..size { offsets.push(Vector2D::new(x as f32 * spacing - offset, y as f32 * spacing - offset)); }
+            }
+            let index = |x: usize, y: usize| y * size + x;
+            for y in 0..size {
+                for x in 0..size {
+                    if x + 1 < size { pairs.push((index(x, y), index(x + 1, y))); }
+                    if y + 1 < size { pairs.push((index(x, y), index(x, y + 1))); }
+                    if x + 1 < size && y + 1 < size {
+                        pairs.push((index(x, y), index(x + 1, y + 1)));
+                        pairs.push((index(x + 1, y), index(x, y + 1)));
+                    }
+                }
+            }
+        }
+        Shape::Circle => {
+            // Rim with spokes to a center particle
+            let size = size.max(3);
+            let radius = size as f32 * spacing / std::f32::consts::TAU;
+            offsets.push(Vector2D::zero());
+            for i in 0..size {
+                let angle = i as f32 / size as f32 * std::f32::consts::TAU;
+                offsets.push(Vector2D::new(angle.cos(), angle.sin()) * radius);
+            }
+            for i in 0..size {
+                pairs.push((0, 1 + i));
+                pairs.push((1 + i, 1 + (i + 1) % size));
+            }
+        }
+    }
+
+    // Clamping single particles onto a wall would squash their bonds to a zero rest length
+    let offsets: Vec<Vector2D<Coordinate>> = offsets.iter().map(|offset| offset.round().cast()).collect();
+    let lower = offsets.iter().fold(Vector2D::splat(Coordinate::MAX), |lower, offset| lower.min(*offset));
+    let upper = offsets.iter().fold(Vector2D::splat(Coordinate::MIN), |upper, offset| upper.max(*offset));
+    if upper.x - lower.x > world.size_field.0 || upper.y - lower.y > world.size_field.1 { return }
+    let center = Point2D::new(center.x.clamp(-lower.x, world.size_field.0 - upper.x), center.y.clamp(-lower.y, world.size_field.1 - upper.y));
+
+    world.edit_entities();
+    let first = world.entities.len();
+    for offset in offsets.iter() {
+        let mut entity = world.new_entity();
+        entity.position = center + *offset;
+        entity.speed = Vector2D::zero();
+        world.entities.push(entity);
+    }
+    for (a, b) in pairs {
+        let rest_length = (offsets[b] - offsets[a]).to_f32().length() / PRECISION as f32;
+        world.bonds.push(Bond { a: first + a, b: first + b, rest_length, stiffness: world.settings.bond_stiffness, damping: world.settings.bond_damping });
+    }
+
+    world.max_particles = world.max_particles.max(world.entities.len() as i32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bond(a: usize, b: usize) -> Bond {
+        Bond { a, b, rest_length: 1.0, stiffness: 1.0, damping: 0.0 }
+    }
+
+    // Particles carry their original index in position.x so that bonds can be checked after the removals
+    #[test]
+    fn remap_follows_removed_particles() {
+        let mut entities: Vec<Entity> = (0..10).map(|i| Entity { position: Point2D::new(i, 0), ..Entity::default() }).collect();
+        let mut bonds: Vec<Bond> = (1..10).map(|i| bond(i - 1, i)).chain([bond(0, 9), bond(2, 7)]).collect();
+        let removed = [0, 3, 4, 8];
+
+        let kept: Vec<bool> = entities.iter().map(|entity| !removed.contains(&entity.position.x)).collect();
+        entities.retain(|entity| !removed.contains(&entity.position.x));
+        remap(&mut bonds, &kept);
+
+        let mut pairs: Vec<(Coordinate, Coordinate)> = bonds.iter().map(|bond| (entities[bond.a].position.x, entities[bond.b].position.x)).collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec![(1, 2), (2, 7), (5, 6), (6, 7)]);
+    }
+
+    #[test]
+    fn sinks_and_the_particle_cap_keep_bonds_on_live_particles() {
+        let mut world = crate::new_world();
+        world.max_particles = 0;
+        world.reset();
+        world.settings.bond_size = 6;
+        let center = Point2D::new(world.size_field.0 / 2, world.size_field.1 / 2);
+        spawn(&mut world, Shape::Grid, center);
+        let pairs: Vec<(Point2D<Coordinate>, Point2D<Coordinate>)> = world.bonds.iter().map(|bond| (world.entities[bond.a].position, world.entities[bond.b].position)).collect();
+
+        // A sink over one corner, then a cap that cuts the last rows
+        world.sinks.push(crate::emitter::Sink { position: world.entities[0].position, radius: 0.5 });
+        world.elapsed_since_update = 0.0;
+        world.update_emitters();
+        assert_eq!(world.entities.len(), 35);
+        world.max_particles = 29;
+        world.truncate_particles();
+
+        assert!(!world.bonds.is_empty());
+        for bond in world.bonds.iter() {
+            assert!(bond.a < world.entities.len() && bond.b < world.entities.len());
+            assert!(pairs.contains(&(world.entities[bond.a].position, world.entities[bond.b].position)));
+        }
+    }
+
+    #[test]
+    fn truncate_drops_bonds_past_the_end() {
+        let mut bonds = vec![bond(0, 1), bond(1, 2), bond(2, 3), bond(3, 0)];
+        truncate(&mut bonds, 3);
+        let pairs: Vec<(usize, usize)> = bonds.iter().map(|bond| (bond.a, bond.b)).collect();
+        assert_eq!(pairs, vec![(0, 1), (1, 2)]);
+    }
+}
\ No newline at end of file
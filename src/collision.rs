@@ -0,0 +1,113 @@
+use crate::Coordinate;
+use crate::entity::Entity;
+use crate::grid::Grid;
+use crate::threadpool::Threadpool;
+use crate::world::Settings;
+
+use euclid::default::Vector2D;
+
+#[derive(Clone, Copy, Default)]
+pub struct Correction {
+    speed: Vector2D<f32>,
+    position: Vector2D<f32>,
+}
+
+// Every entity computes its own response against the previous state, pairs are resolved symmetrically without locks
+// Returns the largest speed correction
+#[allow(clippy::too_many_arguments)]
+pub fn resolve(entities: &mut [Entity], corrections: &mut Vec<Correction>, grid: &Grid, settings: &Settings, size_field: (Coordinate, Coordinate), radius: f32, seed: u64, threadpool: &mut Threadpool) -> f32 {
+    corrections.resize(entities.len(), Correction::default());
+    let diameter = radius * 2.0;
+    let restitution = settings.restitution;
+
+    {
+        let entities = &*entities;
+        threadpool.for_each_chunk_mut(corrections, |_chunk, offset, corrections| {
+            for (i, correction) in corrections.iter_mut().enumerate() {
+                let index = offset + i;
+                let entity = &entities[index];
+                *correction = Correction::default();
+                grid.for_each_neighbour(entities, entity.position, diameter, |other, direction, distance_squared| {
+                    if other == index || distance_squared == 0.0 { return }
+                    let distance = distance_squared.sqrt();
+                    let normal = direction / distance;
+
+                    correction.position -= normal * (diameter - distance) * 0.5;
+
+                    let speed_normal = (entities[other].speed - entity.speed).dot(normal);
+                    if speed_normal < 0.0 {
+                        let mass_total = entity.mass + entities[other].mass;
+                        let mass_ratio = if mass_total > 0.0 { entities[other].mass / mass_total } else { 0.5 };
+                        correction.speed += normal * speed_normal * (1.0 + restitution) * mass_ratio;
+                    }
+                });
+            }
+        });
+    }
+
+    let corrections = &*corrections;
+    threadpool.for_each_chunk_mut(entities, |chunk, offset, entities| {
+        let rng = fastrand::Rng::with_seed(seed ^ chunk as u64);
+        for (i, entity) in entities.iter_mut().enumerate() {
+            let correction = corrections[offset + i];
+            entity.speed += correction.speed;
+            entity.position += correction.position.round().cast();
+            entity.apply_boundaries(settings, size_field, &rng);
+        }
+    });
+    threadpool.map_chunks(corrections.len(), |_chunk, range| {
+        corrections[range].iter().map(|correction| correction.speed.length()).fold(0.0, f32::max)
+    }).into_iter().fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random_coordinate;
+    use euclid::default::Point2D;
+
+    fn resolve_once(entities: &mut [Entity], restitution: f32, radius: f32) {
+        let mut threadpool = Threadpool::new_with_threads(2);
+        let settings = Settings { restitution, ..Settings::default() };
+        let size_field = (1_000_000, 1_000_000);
+        let mut grid = Grid::default();
+        grid.build(entities, size_field, (radius * 2.0).ceil() as Coordinate, (false, false), &mut threadpool);
+        resolve(entities, &mut Vec::new(), &grid, &settings, size_field, radius, 0, &mut threadpool);
+    }
+
+    fn momentum(entities: &[Entity]) -> Vector2D<f64> {
+        entities.iter().map(|entity| entity.speed.to_f64() * entity.mass as f64).sum()
+    }
+
+    #[test]
+    fn crowded_response_conserves_momentum() {
+        let rng = fastrand::Rng::with_seed(9);
+        // A dense patch far from the walls, every particle overlaps several others
+        let mut entities: Vec<Entity> = (0..2_000).map(|_| Entity {
+            position: Point2D::new(random_coordinate(&rng, 450_000..550_000), random_coordinate(&rng, 450_000..550_000)),
+            speed: Vector2D::new(rng.f32() - 0.5, rng.f32() - 0.5) * 20_000.0,
+            mass: 0.5 + rng.f32() * 2.0,
+            ..Entity::default()
+        }).collect();
+        for restitution in [0.0, 0.5, 1.0] {
+            let before = momentum(&entities);
+            let scale: f64 = entities.iter().map(|entity| entity.speed.to_f64().length() * entity.mass as f64).sum();
+            resolve_once(&mut entities, restitution, 3_000.0);
+            assert!((momentum(&entities) - before).length() < scale * 1e-5, "restitution {}", restitution);
+        }
+    }
+
+    #[test]
+    fn elastic_head_on_pair_matches_the_analytic_result() {
+        let (m1, m2, v1, v2) = (1.0, 3.0, 4_000.0, -2_000.0);
+        let mut entities = vec![
+            Entity { position: Point2D::new(499_000, 500_000), speed: Vector2D::new(v1, 0.0), mass: m1, ..Entity::default() },
+            Entity { position: Point2D::new(501_000, 500_000), speed: Vector2D::new(v2, 0.0), mass: m2, ..Entity::default() },
+        ];
+        resolve_once(&mut entities, 1.0, 1_500.0);
+        let expected = [((m1 - m2) * v1 + 2.0 * m2 * v2) / (m1 + m2), ((m2 - m1) * v2 + 2.0 * m1 * v1) / (m1 + m2)];
+        for (entity, expected) in entities.iter().zip(expected) {
+            assert!((entity.speed.x - expected).abs() < 1e-2 && entity.speed.y == 0.0, "{:?} {}", entity.speed, expected);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,595 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::{Entity, GravityCompute, GravityEntity};
+use crate::soa;
+use crate::world::{Backend, Boundary, ChargeSpawn, Settings, SpawnRule, World};
+
+use ash::vk;
+use euclid::default::{Point2D, Vector2D};
+
+const WORKGROUP_SIZE: usize = 256;
+const MODE_STEP: u32 = 0;
+const MODE_VERTICES: u32 = 1;
+const VERIFY_STEPS: u32 = 10;
+// Steps recorded before the command buffer is submitted without waiting for a frame
+const MAX_RECORDED: usize = 64;
+// Largest data vkCmdUpdateBuffer accepts at once
+const UPDATE_CHUNK: usize = 65_536;
+
+// Coordinate is i32 unless large_world, only supported fields reach the device
+#[allow(clippy::unnecessary_cast)]
+fn device_coordinate(value: Coordinate) -> i32 {
+    value.clamp(i32::MIN as Coordinate, i32::MAX as Coordinate) as i32
+}
+
+// Particle as stored in the device local buffer, positions stay i32 whatever the Coordinate type
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Particle {
+    position: [i32; 2],
+    speed: [f32; 2],
+    species: u32,
+    lifetime: f32,
+    mass: f32,
+    charge: f32,
+}
+
+impl Particle {
+    fn new(entity: &Entity) -> Particle {
+        Particle {
+            position: [device_coordinate(entity.position.x), device_coordinate(entity.position.y)],
+            speed: entity.speed.to_array(),
+            species: entity.species,
+            lifetime: entity.lifetime,
+            mass: entity.mass,
+            charge: entity.charge,
+        }
+    }
+
+    fn apply(&self, entity: &mut Entity) {
+        entity.position = Point2D::new(self.position[0] as Coordinate, self.position[1] as Coordinate);
+        entity.speed = Vector2D::from(self.speed);
+    }
+}
+
+// Gravity entity with the constants of its law folded in, laid out like Source in compute.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Source {
+    position: [i32; 2],
+    law: u32,
+    force: f32,
+    parameter: f32,
+    cutoff: f32,
+    max_acceleration: f32,
+    charge: f32,
+    charge_softening_squared: f32,
+    void_1: u32,
+}
+
+impl Source {
+    pub fn new(source: &soa::Source) -> Source {
+        let (law, force, parameter) = source.law.parameters();
+        Source {
+            position: [device_coordinate(source.position.x), device_coordinate(source.position.y)],
+            law,
+            force,
+            parameter,
+            cutoff: source.cutoff.min(f32::MAX),
+            max_acceleration: source.max_acceleration,
+            charge: source.charge,
+            charge_softening_squared: source.charge_softening_squared,
+            void_1: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Parameters {
+    size_field: [i32; 2],
+    camera: [i32; 2],
+    elapsed: f32,
+    max_speed: f32,
+    energy_loss: f32,
+    wall_restitution: f32,
+    wall_margin: f32,
+    wall_repulsion: f32,
+    boundary_x: u32,
+    boundary_y: u32,
+    spawn_rule: u32,
+    count: u32,
+    num_sources: u32,
+    seed: u32,
+    pixel: u32,
+    mode: u32,
+}
+
+impl Parameters {
+    fn new(settings: &Settings, size_field: (Coordinate, Coordinate), count: usize) -> Parameters {
+        let boundary = |boundary: Boundary| Boundary::ALL.iter().position(|b| *b == boundary).unwrap() as u32;
+        Parameters {
+            size_field: [device_coordinate(size_field.0), device_coordinate(size_field.1)],
+            max_speed: settings.max_speed * PRECISION as f32,
+            energy_loss: settings.energy_loss,
+            wall_restitution: settings.wall_restitution,
+            wall_margin: settings.wall_margin * PRECISION as f32,
+            wall_repulsion: settings.wall_repulsion * PRECISION as f32,
+            boundary_x: boundary(settings.boundary_x),
+            boundary_y: boundary(settings.boundary_y),
+            spawn_rule: SpawnRule::ALL.iter().position(|rule| *rule == settings.spawn_rule).unwrap() as u32,
+            count: count as u32,
+            pixel: PRECISION as u32,
+            ..Default::default()
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self as *const Parameters as *const u8, std::mem::size_of::<Parameters>()) }
+    }
+}
+
+struct Buffer {
+    raw: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+}
+
+impl Buffer {
+    fn new(device: &ash::Device, memory_properties: &vk::PhysicalDeviceMemoryProperties, size: u64, usage: vk::BufferUsageFlags, flags: vk::MemoryPropertyFlags, queue_families: &[u32]) -> Buffer {
+        unsafe {
+            let sharing_mode = if queue_families.len() > 1 { vk::SharingMode::CONCURRENT } else { vk::SharingMode::EXCLUSIVE };
+            let buffer_info = vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(usage)
+                .sharing_mode(sharing_mode)
+                .queue_family_indices(queue_families);
+            let raw = device.create_buffer(&buffer_info, None).unwrap();
+            let requirements = device.get_buffer_memory_requirements(raw);
+            let memory_type = (0..memory_properties.memory_type_count as usize)
+                .find(|index| {
+                    memory_properties.memory_types[*index].property_flags.contains(flags) &&
+                    ((1 << *index) & requirements.memory_type_bits != 0)
+                })
+                .unwrap();
+            let allocate_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type as u32);
+            let memory = device.allocate_memory(&allocate_info, None).unwrap();
+            device.bind_buffer_memory(raw, memory, 0).unwrap();
+            Buffer { raw, memory, size }
+        }
+    }
+
+    fn write<T: Copy>(&self, device: &ash::Device, data: &[T]) {
+        unsafe {
+            let mapped_memory = device.map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty()).unwrap() as *mut T;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), mapped_memory, data.len());
+            device.unmap_memory(self.memory);
+        }
+    }
+
+    fn read<T: Copy>(&self, device: &ash::Device, data: &mut [T]) {
+        unsafe {
+            let mapped_memory = device.map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty()).unwrap() as *const T;
+            std::ptr::copy_nonoverlapping(mapped_memory, data.as_mut_ptr(), data.len());
+            device.unmap_memory(self.memory);
+        }
+    }
+
+    fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_buffer(self.raw, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+// Particles living in device local memory, advanced by compute.glsl and drawn straight from its vertex buffer
+pub struct Compute {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    queue_families: Vec<u32>,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    // Commands recorded since the last submission, and the last submission which holds the fence
+    recording: Option<vk::CommandBuffer>,
+    recorded: usize,
+    in_flight: Option<vk::CommandBuffer>,
+    fence: vk::Fence,
+    // The renderer reads the vertices on the same queue, so submission order is enough
+    shared_queue: bool,
+    shader: vk::ShaderModule,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    particles: Buffer,
+    vertices: Buffer,
+    sources: Buffer,
+    staging: Buffer,
+    count: usize,
+    resident: bool,
+}
+
+impl Compute {
+    pub fn new(device: &ash_ez::Device, physical_device: &ash_ez::PhysicalDevice) -> Compute {
+        unsafe {
+            let mut queue_families = vec![device.compute_queue.family_index];
+            if device.graphic_queue.family_index != device.compute_queue.family_index { queue_families.push(device.graphic_queue.family_index); }
+            let queue = device.raw.get_device_queue(device.compute_queue.family_index, 0);
+
+            let command_pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                .queue_family_index(device.compute_queue.family_index);
+            let command_pool = device.raw.create_command_pool(&command_pool_info, None).unwrap();
+            let fence = device.raw.create_fence(&vk::FenceCreateInfo::default(), None).unwrap();
+
+            let shader_raw_u8 = include_bytes!("../shaders/compiled/compute.spv");
+            let shader_raw = std::slice::from_raw_parts(shader_raw_u8.as_ptr() as *const u32, shader_raw_u8.len() / 4);
+            let shader = device.create_shader(shader_raw);
+
+            let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..3).map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build()
+            }).collect();
+            let descriptor_set_layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings);
+            let descriptor_set_layout = device.raw.create_descriptor_set_layout(&descriptor_set_layout_create_info, None).unwrap();
+            let descriptor_set_layouts = [descriptor_set_layout];
+
+            let descriptor_pool_sizes = [vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(3)
+                .build()];
+            let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(1)
+                .pool_sizes(&descriptor_pool_sizes);
+            let descriptor_pool = device.raw.create_descriptor_pool(&descriptor_pool_create_info, None).unwrap();
+            let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&descriptor_set_layouts);
+            let descriptor_set = device.raw.allocate_descriptor_sets(&descriptor_set_allocate_info).unwrap()[0];
+
+            let push_constant_ranges = [vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<Parameters>() as u32,
+            }];
+            let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&descriptor_set_layouts)
+                .push_constant_ranges(&push_constant_ranges);
+            let pipeline_layout = device.raw.create_pipeline_layout(&pipeline_layout_info, None).unwrap();
+
+            let main_str = std::ffi::CString::new("main").unwrap();
+            let stage = ash_ez::utils::pipeline_shader_stage_create_info_helper(shader, vk::ShaderStageFlags::COMPUTE, &main_str);
+            let pipeline_create_infos = [vk::ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(pipeline_layout)
+                .build()];
+            let pipeline = device.raw.create_compute_pipelines(vk::PipelineCache::null(), &pipeline_create_infos, None).unwrap()[0];
+
+            let memory_properties = physical_device.memory_properties;
+            let mut compute = Compute {
+                particles: Buffer::new(&device.raw, &memory_properties, 256, Compute::particles_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, &queue_families),
+                vertices: Buffer::new(&device.raw, &memory_properties, 256, Compute::vertices_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, &queue_families),
+                sources: Buffer::new(&device.raw, &memory_properties, 256, Compute::sources_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, &queue_families),
+                staging: Buffer::new(&device.raw, &memory_properties, 256, Compute::staging_usage(), Compute::host_flags(), &queue_families),
+                device: device.raw.clone(),
+                memory_properties,
+                queue_families,
+                queue,
+                command_pool,
+                recording: None,
+                recorded: 0,
+                in_flight: None,
+                fence,
+                shared_queue: device.graphic_queue.family_index == device.compute_queue.family_index,
+                shader,
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_set,
+                pipeline_layout,
+                pipeline,
+                count: 0,
+                resident: false,
+            };
+            compute.update_descriptor_set();
+            compute
+        }
+    }
+
+    fn particles_usage() -> vk::BufferUsageFlags { vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST }
+    fn vertices_usage() -> vk::BufferUsageFlags { vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER }
+    fn sources_usage() -> vk::BufferUsageFlags { vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST }
+    fn staging_usage() -> vk::BufferUsageFlags { vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST }
+    fn host_flags() -> vk::MemoryPropertyFlags { vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT }
+
+    // Positions are uploaded as i32, larger fields stay on the CPU
+    pub fn supports(size_field: (Coordinate, Coordinate)) -> bool {
+        size_field.0.max(size_field.1) <= (i32::MAX / 2) as Coordinate
+    }
+
+    // True when the device buffer holds the current particles and World::entities is stale
+    pub fn resident(&self) -> bool {
+        self.resident
+    }
+
+    pub fn vertex_buffer(&self) -> (vk::Buffer, usize) {
+        (self.vertices.raw, self.count)
+    }
+
+    // Grows a buffer to a power of two size, the descriptor set must be updated afterwards
+    fn reserve(device: &ash::Device, memory_properties: &vk::PhysicalDeviceMemoryProperties, buffer: &mut Buffer, size: u64, usage: vk::BufferUsageFlags, flags: vk::MemoryPropertyFlags, queue_families: &[u32]) -> bool {
+        if buffer.size >= size { return false }
+        buffer.destroy(device);
+        *buffer = Buffer::new(device, memory_properties, size.next_power_of_two(), usage, flags, queue_families);
+        true
+    }
+
+    fn reserve_particles(&mut self, count: usize) {
+        let (device, memory_properties, queue_families) = (&self.device, &self.memory_properties, &self.queue_families);
+        let particles_size = (count.max(1) * std::mem::size_of::<Particle>()) as u64;
+        let vertices_size = (count.max(1) * std::mem::size_of::<crate::renderer::ParticleVertex>()) as u64;
+        let mut changed = Compute::reserve(device, memory_properties, &mut self.particles, particles_size, Compute::particles_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, queue_families);
+        changed |= Compute::reserve(device, memory_properties, &mut self.vertices, vertices_size, Compute::vertices_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, queue_families);
+        Compute::reserve(device, memory_properties, &mut self.staging, particles_size, Compute::staging_usage(), Compute::host_flags(), queue_families);
+        if changed { self.update_descriptor_set(); }
+    }
+
+    fn update_descriptor_set(&mut self) {
+        let buffers = [&self.particles, &self.vertices, &self.sources];
+        let buffer_infos: Vec<[vk::DescriptorBufferInfo; 1]> = buffers.iter().map(|buffer| [vk::DescriptorBufferInfo {
+            buffer: buffer.raw,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }]).collect();
+        let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = buffer_infos.iter().enumerate().map(|(binding, buffer_info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(self.descriptor_set)
+                .dst_binding(binding as u32)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(buffer_info)
+                .build()
+        }).collect();
+        unsafe { self.device.update_descriptor_sets(&write_descriptor_sets, &[]); }
+    }
+
+    // Appends to the open command buffer, which is submitted once per frame instead of once per dispatch
+    fn record<F: FnOnce(&Compute, vk::CommandBuffer)>(&mut self, record: F) {
+        unsafe {
+            let command_buffer = match self.recording {
+                Some(command_buffer) => { command_buffer }
+                None => {
+                    let command_buffer_info = vk::CommandBufferAllocateInfo::builder()
+                        .command_pool(self.command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1);
+                    let command_buffer = self.device.allocate_command_buffers(&command_buffer_info).unwrap()[0];
+                    let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+                    self.device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+                    self.recording = Some(command_buffer);
+                    command_buffer
+                }
+            };
+            record(self, command_buffer);
+            // Makes the writes visible to the next dispatch, copy or vertex fetch
+            let memory_barriers = [vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                .build()];
+            self.device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::ALL_COMMANDS | vk::PipelineStageFlags::HOST, vk::DependencyFlags::empty(), &memory_barriers, &[], &[]);
+        }
+        self.recorded += 1;
+        if self.recorded >= MAX_RECORDED { self.flush(); }
+    }
+
+    // Submits the recorded commands, the previous submission is waited on first since there is a single fence
+    fn flush(&mut self) {
+        if let Some(command_buffer) = self.recording.take() {
+            self.recorded = 0;
+            self.wait();
+            unsafe {
+                self.device.end_command_buffer(command_buffer).unwrap();
+                let command_buffers = [command_buffer];
+                let submit_infos = [vk::SubmitInfo::builder()
+                    .command_buffers(&command_buffers)
+                    .build()];
+                self.device.queue_submit(self.queue, &submit_infos, self.fence).unwrap();
+            }
+            self.in_flight = Some(command_buffer);
+        }
+    }
+
+    // Blocks until the last submission is done, only needed before the host touches or reallocates a buffer the device uses
+    fn wait(&mut self) {
+        if let Some(command_buffer) = self.in_flight.take() {
+            unsafe {
+                self.device.wait_for_fences(&[self.fence], true, u64::MAX).unwrap();
+                self.device.reset_fences(&[self.fence]).unwrap();
+                self.device.free_command_buffers(self.command_pool, &[command_buffer]);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.flush();
+        self.wait();
+    }
+
+    fn dispatch(&mut self, parameters: &Parameters) {
+        if parameters.count == 0 { return }
+        self.record(|compute, command_buffer| unsafe {
+            compute.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, compute.pipeline);
+            compute.device.cmd_bind_descriptor_sets(command_buffer, vk::PipelineBindPoint::COMPUTE, compute.pipeline_layout, 0, &[compute.descriptor_set], &[]);
+            compute.device.cmd_push_constants(command_buffer, compute.pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, parameters.as_bytes());
+            compute.device.cmd_dispatch(command_buffer, (parameters.count as usize).div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+        });
+    }
+
+    pub fn upload(&mut self, entities: &[Entity]) {
+        self.finish();
+        self.reserve_particles(entities.len());
+        self.count = entities.len();
+        self.resident = true;
+        if self.count == 0 { return }
+
+        let particles: Vec<Particle> = entities.iter().map(Particle::new).collect();
+        self.staging.write(&self.device, &particles);
+        let size = (self.count * std::mem::size_of::<Particle>()) as u64;
+        self.record(|compute, command_buffer| unsafe {
+            compute.device.cmd_copy_buffer(command_buffer, compute.staging.raw, compute.particles.raw, &[vk::BufferCopy { src_offset: 0, dst_offset: 0, size }]);
+        });
+    }
+
+    // Brings the particles back into the entities, which become the current state again
+    pub fn download(&mut self, entities: &mut [Entity]) {
+        self.resident = false;
+        if self.count == 0 { return }
+
+        let size = (self.count * std::mem::size_of::<Particle>()) as u64;
+        self.record(|compute, command_buffer| unsafe {
+            compute.device.cmd_copy_buffer(command_buffer, compute.particles.raw, compute.staging.raw, &[vk::BufferCopy { src_offset: 0, dst_offset: 0, size }]);
+        });
+        self.finish();
+        let mut particles = vec![Particle::default(); self.count];
+        self.staging.read(&self.device, &mut particles);
+        entities.iter_mut().zip(particles.iter()).for_each(|(entity, particle)| particle.apply(entity));
+    }
+
+    // The entities were replaced on the CPU, they are uploaded again on the next step
+    pub fn invalidate(&mut self) {
+        self.resident = false;
+    }
+
+    pub fn step(&mut self, sources: &[Source], settings: &Settings, size_field: (Coordinate, Coordinate), elapsed: f32, seed: u64) {
+        let size = (sources.len().max(1) * std::mem::size_of::<Source>()) as u64;
+        if self.sources.size < size {
+            // The recorded commands and the descriptor set still refer to the old buffer
+            self.finish();
+            Compute::reserve(&self.device, &self.memory_properties, &mut self.sources, size, Compute::sources_usage(), vk::MemoryPropertyFlags::DEVICE_LOCAL, &self.queue_families);
+            self.update_descriptor_set();
+        }
+        // Written inline in the command buffer, so every recorded step keeps its own sources
+        let bytes = unsafe { std::slice::from_raw_parts(sources.as_ptr() as *const u8, std::mem::size_of_val(sources)) };
+        if !bytes.is_empty() {
+            self.record(|compute, command_buffer| unsafe {
+                for (index, chunk) in bytes.chunks(UPDATE_CHUNK).enumerate() {
+                    compute.device.cmd_update_buffer(command_buffer, compute.sources.raw, (index * UPDATE_CHUNK) as u64, chunk);
+                }
+            });
+        }
+
+        let parameters = Parameters {
+            elapsed,
+            num_sources: sources.len() as u32,
+            seed: (seed ^ (seed >> 32)) as u32,
+            mode: MODE_STEP,
+            ..Parameters::new(settings, size_field, self.count)
+        };
+        self.dispatch(&parameters);
+    }
+
+    pub fn update_vertices(&mut self, camera: Point2D<Coordinate>, settings: &Settings, size_field: (Coordinate, Coordinate)) {
+        let parameters = Parameters {
+            camera: [device_coordinate(camera.x), device_coordinate(camera.y)],
+            mode: MODE_VERTICES,
+            ..Parameters::new(settings, size_field, self.count)
+        };
+        self.dispatch(&parameters);
+        self.flush();
+        // A graphics queue of another family is not ordered after this submission
+        if !self.shared_queue { self.wait(); }
+    }
+
+    pub fn destroy(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            for buffer in [&self.particles, &self.vertices, &self.sources, &self.staging] { buffer.destroy(&self.device); }
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.device.destroy_shader_module(self.shader, None);
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
+
+// Headless comparison of the compute backend with the CPU step, run with --verify-gpu
+pub fn verify(mut world: Box<World>) {
+    let instance = unsafe { ash_ez::Instance::new() };
+    let physical_devices = unsafe { instance.enumerate_physical_devices() };
+    let physical_device = match physical_devices.iter().filter(|physical_device| physical_device.has_compute_queue()).min_by_key(|physical_device| physical_device.preference()) {
+        Some(physical_device) => { physical_device.clone() }
+        None => {
+            eprintln!("no Vulkan device with a compute queue");
+            std::process::exit(1);
+        }
+    };
+    let name = unsafe { std::ffi::CStr::from_ptr(physical_device.properties.device_name.as_ptr()) };
+    let device = unsafe { instance.create_device_headless(&physical_device, vk::PhysicalDeviceFeatures::default(), Vec::new()) };
+    world.compute = Some(Compute::new(&device, &physical_device));
+
+    world.max_particles = 2i32.pow(14);
+    world.settings.diagnostics = false;
+    world.settings.simd = false;
+    world.settings.charge_spawn = ChargeSpawn::Uniform;
+    world.elapsed_since_update = 1.0 / 120.0;
+
+    println!("device = {}, particles = {}, steps = {}", name.to_string_lossy(), world.max_particles, VERIFY_STEPS);
+    println!("{:>14} {:>8} {:>12} {:>12} {:>10} {:>10}", "law", "boundary", "position_px", "speed_ratio", "cpu_ms", "gpu_ms");
+    let mut failed = false;
+    for (index, name) in GravityCompute::NAMES.iter().enumerate() {
+        let boundary = [Boundary::Reflect, Boundary::Wrap, Boundary::Soft][index % 3];
+        world.settings.boundary_x = boundary;
+        world.settings.boundary_y = boundary;
+        world.reset();
+        for side in [1, 2] {
+            let mut gravity = GravityEntity::default();
+            gravity.inner.position = Point2D::new(world.size_field.0 * side / 3, world.size_field.1 / 2);
+            gravity.inner.charge = 1.0;
+            gravity.gravity.compute = GravityCompute::from_index(index, 100.0);
+            world.entities_gravity.push(gravity);
+        }
+        let initial = world.entities.clone();
+
+        world.settings.backend = Backend::Cpu;
+        let time = std::time::Instant::now();
+        for _ in 0..VERIFY_STEPS { world.step(); }
+        let cpu_time = time.elapsed().as_secs_f64() * 1_000.0 / VERIFY_STEPS as f64;
+        let expected = std::mem::replace(&mut world.entities, initial);
+
+        world.step_count = 0;
+        world.compute.as_mut().unwrap().invalidate();
+        world.settings.backend = Backend::Gpu;
+        let time = std::time::Instant::now();
+        for _ in 0..VERIFY_STEPS { world.step(); }
+        world.edit_entities();
+        let gpu_time = time.elapsed().as_secs_f64() * 1_000.0 / VERIFY_STEPS as f64;
+
+        let max_speed = world.settings.max_speed * PRECISION as f32;
+        let (mut position_error, mut speed_error): (f32, f32) = (0.0, 0.0);
+        for (cpu, gpu) in expected.iter().zip(world.entities.iter()) {
+            position_error = position_error.max((cpu.position - gpu.position).to_f32().length() / PRECISION as f32);
+            speed_error = speed_error.max((cpu.speed - gpu.speed).length() / cpu.speed.length().max(max_speed * 0.000_1));
+        }
+        let ok = position_error <= 1.0 && speed_error <= 0.001;
+        failed |= !ok;
+        println!("{:>14} {:>8} {:>12.4} {:>12.2e} {:>10.2} {:>10.2} {}", name, boundary.name(), position_error, speed_error, cpu_time, gpu_time, if ok { "ok" } else { "MISMATCH" });
+    }
+
+    if let Some(mut compute) = world.compute.take() { compute.destroy(); }
+    unsafe {
+        device.destroy();
+        instance.destroy();
+    }
+    if failed { std::process::exit(1); }
+}
\ No newline at end of file
@@ -0,0 +1,92 @@
+use crate::PRECISION;
+use crate::entity::Entity;
+
+use euclid::default::Vector2D;
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+// Partial sums of one worker chunk, positions and speeds in pixels
+#[derive(Clone, Copy, Default)]
+pub struct Sums {
+    pub count: u64,
+    pub mass: f64,
+    pub kinetic: f64,
+    pub potential: f64,
+    pub momentum: Vector2D<f64>,
+    pub moment: Vector2D<f64>,
+    pub angular_momentum: f64,
+}
+
+impl Sums {
+    pub fn add(&mut self, entity: &Entity, potential: f32) {
+        let mass = entity.mass as f64;
+        let position = entity.position.to_f64().to_vector() / PRECISION as f64;
+        let speed = entity.speed.to_f64() / PRECISION as f64;
+        self.count += 1;
+        self.mass += mass;
+        self.kinetic += 0.5 * mass * speed.square_length();
+        self.potential += potential as f64 / (PRECISION as f64).powi(2);
+        self.momentum += speed * mass;
+        self.moment += position * mass;
+        self.angular_momentum += position.cross(speed) * mass;
+    }
+
+    pub fn merge(&mut self, other: &Sums) {
+        self.count += other.count;
+        self.mass += other.mass;
+        self.kinetic += other.kinetic;
+        self.potential += other.potential;
+        self.momentum += other.momentum;
+        self.moment += other.moment;
+        self.angular_momentum += other.angular_momentum;
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Diagnostics {
+    pub step: u64,
+    pub kinetic: f32,
+    pub potential: f32,
+    pub momentum: Vector2D<f32>,
+    pub angular_momentum: f32,
+    pub dispersion: f32,
+    pub temperature: f32,
+}
+
+impl Diagnostics {
+    pub const HEADER: &'static str = "step,kinetic,potential,total,momentum_x,momentum_y,angular_momentum,dispersion,temperature";
+
+    // Angular momentum and dispersion are taken relative to the center of mass
+    pub fn from_sums(step: u64, sums: &Sums) -> Diagnostics {
+        if sums.count == 0 || sums.mass <= 0.0 { return Diagnostics { step, ..Default::default() } }
+        let center_speed = sums.momentum / sums.mass;
+        let internal_kinetic = (sums.kinetic - 0.5 * sums.mass * center_speed.square_length()).max(0.0);
+        Diagnostics {
+            step,
+            kinetic: sums.kinetic as f32,
+            potential: sums.potential as f32,
+            momentum: sums.momentum.to_f32(),
+            angular_momentum: (sums.angular_momentum - sums.moment.cross(sums.momentum) / sums.mass) as f32,
+            dispersion: (2.0 * internal_kinetic / sums.mass).sqrt() as f32,
+            temperature: (internal_kinetic / sums.count as f64) as f32,
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+
+    pub fn csv_line(&self) -> String {
+        format!("{},{},{},{},{},{},{},{},{}", self.step, self.kinetic, self.potential, self.total(), self.momentum.x, self.momentum.y, self.angular_momentum, self.dispersion, self.temperature)
+    }
+}
+
+pub fn export_csv(history: &VecDeque<Diagnostics>, path: &str) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "{}", Diagnostics::HEADER)?;
+    for diagnostics in history.iter() {
+        writeln!(file, "{}", diagnostics.csv_line())?;
+    }
+    file.flush()
+}
\ No newline at end of file
@@ -0,0 +1,92 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::Entity;
+use crate::world::Settings;
+
+use euclid::default::{Point2D, Vector2D};
+
+#[derive(Clone)]
+pub struct Emitter {
+    pub position: Point2D<Coordinate>,
+    pub rate: f32,
+    pub spread: f32,
+    pub direction: f32,
+    pub cone: f32,
+    pub speed: f32,
+    pub lifetime: f32,
+    pub accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(position: Point2D<Coordinate>) -> Emitter {
+        Emitter {
+            position,
+            rate: 1_000.0,
+            spread: 5.0,
+            direction: std::f32::consts::FRAC_PI_2,
+            cone: 0.3,
+            speed: 100.0,
+            lifetime: 10.0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn emit(&mut self, elapsed: f32, room: usize, settings: &Settings, rng: &fastrand::Rng, output: &mut Vec<Entity>) {
+        self.accumulator += self.rate * elapsed;
+        let count = (self.accumulator as usize).min(room);
+        self.accumulator -= self.accumulator.floor();
+
+        for _ in 0..count {
+            let offset_angle = rng.f32() * std::f32::consts::TAU;
+            let offset_length = rng.f32().sqrt() * self.spread * PRECISION as f32;
+            let angle = self.direction + (rng.f32() * 2.0 - 1.0) * self.cone;
+            let mut entity = Entity {
+                position: self.position + (Vector2D::new(offset_angle.cos(), offset_angle.sin()) * offset_length).round().cast(),
+                speed: Vector2D::new(angle.cos(), angle.sin()) * self.speed * PRECISION as f32,
+                lifetime: self.lifetime,
+                ..Default::default()
+            };
+            settings.spawn_attributes(&mut entity, rng);
+            output.push(entity);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Sink {
+    pub position: Point2D<Coordinate>,
+    pub radius: f32,
+}
+
+impl Sink {
+    pub fn new(position: Point2D<Coordinate>) -> Sink {
+        Sink { position, radius: 20.0 }
+    }
+
+    // Measured through the seam on wrapped axes
+    pub fn contains(&self, position: Point2D<Coordinate>, settings: &Settings, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) -> bool {
+        let radius = self.radius * PRECISION as f32;
+        Entity::direction_to(position, self.position, settings, size_field, rng).to_f32().square_length() < radius * radius
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Boundary;
+
+    #[test]
+    fn sink_reaches_across_wrapped_seam() {
+        let rng = fastrand::Rng::with_seed(1);
+        let mut settings = Settings::default();
+        let size_field = (1_000_000, 500_000);
+        let sink = Sink::new(Point2D::new(5_000, 250_000));
+        let across = Point2D::new(size_field.0 - 5_000, 250_000);
+
+        settings.boundary_x = Boundary::Wrap;
+        assert!(sink.contains(across, &settings, size_field, &rng));
+        assert!(!sink.contains(Point2D::new(500_000, 250_000), &settings, size_field, &rng));
+        settings.boundary_x = Boundary::Reflect;
+        assert!(!sink.contains(across, &settings, size_field, &rng));
+    }
+}
\ No newline at end of file
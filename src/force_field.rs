@@ -0,0 +1,85 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::{Entity, GravitySettings};
+use crate::world::Settings;
+
+use euclid::default::{Point2D, Vector2D};
+
+pub const CURL_WAVES: usize = 8;
+
+#[derive(Clone)]
+pub enum ForceField {
+    Line { start: Point2D<Coordinate>, end: Point2D<Coordinate>, gravity: GravitySettings },
+    Ring { center: Point2D<Coordinate>, radius: f32, gravity: GravitySettings },
+    Wind { min: Point2D<Coordinate>, max: Point2D<Coordinate>, angle: f32, force: f32 },
+    CurlNoise { scale: f32, speed: f32, force: f32, time: f32, waves: [(f32, f32, f32, f32); CURL_WAVES] },
+}
+
+impl ForceField {
+    pub const NAMES: [&'static str; 4] = ["line", "ring", "wind", "curl_noise"];
+
+    pub fn new(index: usize, center: Point2D<Coordinate>, rng: &fastrand::Rng) -> ForceField {
+        let gravity = GravitySettings { compute: crate::entity::GravityCompute::Linear(10.0), cutoff: None };
+        let half_size = 100 * PRECISION;
+        match index {
+            0 => { ForceField::Line { start: center - Vector2D::new(half_size, 0), end: center + Vector2D::new(half_size, 0), gravity } }
+            1 => { ForceField::Ring { center, radius: 100.0, gravity } }
+            2 => { ForceField::Wind { min: center - Vector2D::new(half_size, half_size), max: center + Vector2D::new(half_size, half_size), angle: 0.0, force: 100.0 } }
+            _ => {
+                // Plane waves of random direction, frequency and phase, the curl of their sum is the flow
+                let mut waves = [(0.0, 0.0, 0.0, 0.0); CURL_WAVES];
+                for wave in waves.iter_mut() {
+                    let angle = rng.f32() * std::f32::consts::TAU;
+                    let frequency = 0.5 + rng.f32() * 1.5;
+                    *wave = (angle.cos() * frequency, angle.sin() * frequency, rng.f32() * std::f32::consts::TAU, rng.f32() * 2.0 - 1.0);
+                }
+                ForceField::CurlNoise { scale: 100.0, speed: 1.0, force: 100.0, time: 0.0, waves }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForceField::Line { .. } => { ForceField::NAMES[0] }
+            ForceField::Ring { .. } => { ForceField::NAMES[1] }
+            ForceField::Wind { .. } => { ForceField::NAMES[2] }
+            ForceField::CurlNoise { .. } => { ForceField::NAMES[3] }
+        }
+    }
+
+    pub fn advance(&mut self, elapsed: f32) {
+        if let ForceField::CurlNoise { speed, time, .. } = self {
+            *time += *speed * elapsed;
+        }
+    }
+
+    pub fn acceleration(&self, position: Point2D<Coordinate>, settings: &Settings, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) -> Vector2D<f32> {
+        match self {
+            ForceField::Line { start, end, gravity } => {
+                let segment = (*end - *start).to_f32();
+                let relative = (position - *start).to_f32();
+                let t = if segment.square_length() > 0.0 { (relative.dot(segment) / segment.square_length()).clamp(0.0, 1.0) } else { 0.0 };
+                let closest = *start + (segment * t).round().cast();
+                Entity::source_acceleration(position, closest, gravity, settings, size_field, rng)
+            }
+            ForceField::Ring { center, radius, gravity } => {
+                let relative = (position - *center).to_f32();
+                let direction = if relative.square_length() > 0.0 { relative.normalize() } else { Vector2D::new(1.0, 0.0) };
+                let closest = *center + (direction * *radius * PRECISION as f32).round().cast();
+                Entity::source_acceleration(position, closest, gravity, settings, size_field, rng)
+            }
+            ForceField::Wind { min, max, angle, force } => {
+                if position.x < min.x || position.y < min.y || position.x > max.x || position.y > max.y { return Vector2D::zero() }
+                Vector2D::new(angle.cos(), angle.sin()) * *force * PRECISION as f32
+            }
+            ForceField::CurlNoise { scale, force, time, waves, .. } => {
+                let position = position.to_f32() / (*scale * PRECISION as f32);
+                let mut flow = Vector2D::zero();
+                for (kx, ky, phase, drift) in waves.iter() {
+                    let derivative = (kx * position.x + ky * position.y + phase + drift * time).cos();
+                    flow += Vector2D::new(*ky, -*kx) * derivative;
+                }
+                flow / (CURL_WAVES as f32).sqrt() * *force * PRECISION as f32
+            }
+        }
+    }
+}
\ No newline at end of file
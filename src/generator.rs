@@ -0,0 +1,163 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::{Entity, GravityCompute, GravityEntity};
+use crate::world::World;
+
+use euclid::default::{Point2D, Vector2D};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Generator {
+    Uniform,
+    Disc,
+    Rings,
+    Gaussian,
+    Lattice,
+    Galaxies,
+}
+
+impl Generator {
+    pub const ALL: [Generator; 6] = [Generator::Uniform, Generator::Disc, Generator::Rings, Generator::Gaussian, Generator::Lattice, Generator::Galaxies];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Uniform => { "uniform" }
+            Generator::Disc => { "disc" }
+            Generator::Rings => { "rings" }
+            Generator::Gaussian => { "gaussian" }
+            Generator::Lattice => { "lattice" }
+            Generator::Galaxies => { "galaxies" }
+        }
+    }
+}
+
+pub fn generate(world: &mut World) {
+    let count = world.max_particles.max(0) as usize;
+    let center = Point2D::new(world.size_field.0 / 2, world.size_field.1 / 2);
+    let settings = &world.settings;
+    let radius = settings.generator_radius * PRECISION as f32;
+    let inner_radius = settings.generator_inner_radius.min(settings.generator_radius) * PRECISION as f32;
+
+    match settings.generator {
+        Generator::Uniform => {
+            for _ in 0..count {
+                let entity = world.new_entity();
+                world.entities.push(entity);
+            }
+        }
+        Generator::Disc | Generator::Rings => {
+            let rings = settings.generator_rings.max(1);
+            let discrete = settings.generator == Generator::Rings;
+            let index = orbit_center(world, center);
+            let gravity = world.entities_gravity[index].clone();
+            for i in 0..count {
+                let distance = if discrete {
+                    let ring = (i as u32 % rings) as f32;
+                    inner_radius + (radius - inner_radius) * (ring + 0.5) / rings as f32
+                } else {
+                    (world.rng.f32() * (radius * radius - inner_radius * inner_radius) + inner_radius * inner_radius).sqrt()
+                };
+                let entity = orbiting_entity(world, &gravity, distance);
+                world.entities.push(entity);
+            }
+        }
+        Generator::Gaussian => {
+            // Samples outside the field are drawn again, capping sigma to the smaller side keeps most of them inside
+            let sigma = (settings.generator_sigma * PRECISION as f32).min(world.size_field.0.min(world.size_field.1) as f32);
+            for _ in 0..count {
+                let mut entity = world.new_entity();
+                loop {
+                    // Box-Muller transform
+                    let length = (-2.0 * (1.0 - world.rng.f32()).ln()).sqrt() * sigma;
+                    let angle = world.rng.f32() * std::f32::consts::TAU;
+                    entity.position = center + (Vector2D::new(angle.cos(), angle.sin()) * length).round().cast();
+                    if inside(entity.position, world.size_field) { break }
+                }
+                world.entities.push(entity);
+            }
+        }
+        Generator::Lattice => {
+            // The spacing shrinks when the lattice would not fit, positions are clamped once it is down to one unit
+            let side = (count as f64).sqrt().ceil().max(1.0) as Coordinate;
+            let fit = (world.size_field.0.min(world.size_field.1) / side).max(1);
+            let spacing = ((settings.generator_spacing * PRECISION as f32) as Coordinate).clamp(1, fit);
+            let origin = center - Vector2D::new(side * spacing / 2, side * spacing / 2);
+            let max = Point2D::new(world.size_field.0 - 1, world.size_field.1 - 1);
+            for i in 0..count as Coordinate {
+                let mut entity = world.new_entity();
+                entity.position = (origin + Vector2D::new(i % side, i / side) * spacing).clamp(Point2D::zero(), max);
+                world.entities.push(entity);
+            }
+        }
+        Generator::Galaxies => {
+            let offset = Vector2D::new(settings.generator_separation * 0.5, settings.generator_separation * 0.25) * PRECISION as f32;
+            let speed = settings.generator_speed * PRECISION as f32;
+            world.entities_gravity.retain(|gravity| !gravity.generated);
+            for side in [-1.0, 1.0] {
+                world.entities_gravity.push(GravityEntity {
+                    inner: Entity {
+                        position: center + (offset * side).round().cast(),
+                        speed: Vector2D::new(-speed * side, 0.0),
+                        ..Default::default()
+                    },
+                    gravity: crate::entity::GravitySettings { compute: GravityCompute::Softened { force: 100.0, softening: 5.0 }, cutoff: None },
+                    movable: true,
+                    generated: true,
+                });
+            }
+            let len = world.entities_gravity.len();
+            let galaxies = [world.entities_gravity[len - 2].clone(), world.entities_gravity[len - 1].clone()];
+            for i in 0..count {
+                let distance = (world.rng.f32() * (radius * radius - inner_radius * inner_radius) + inner_radius * inner_radius).sqrt();
+                let entity = orbiting_entity(world, &galaxies[i % 2], distance);
+                world.entities.push(entity);
+            }
+        }
+    }
+}
+
+fn inside(position: Point2D<Coordinate>, size_field: (Coordinate, Coordinate)) -> bool {
+    (0..size_field.0).contains(&position.x) && (0..size_field.1).contains(&position.y)
+}
+
+// Index of the chosen gravity entity, a static one is created at the center when there is none
+fn orbit_center(world: &mut World, center: Point2D<Coordinate>) -> usize {
+    if world.entities_gravity.is_empty() {
+        let mut gravity = GravityEntity::default();
+        gravity.inner.position = center;
+        gravity.gravity.compute = GravityCompute::Softened { force: 100.0, softening: 5.0 };
+        world.entities_gravity.push(gravity);
+    }
+    (world.settings.generator_gravity as usize).min(world.entities_gravity.len() - 1)
+}
+
+// Circular orbit speed from the radial pull of the gravity entity at that distance
+fn orbiting_entity(world: &World, gravity: &GravityEntity, distance: f32) -> Entity {
+    let angle = world.rng.f32() * std::f32::consts::TAU;
+    let radial = Vector2D::new(angle.cos(), angle.sin());
+    let mut entity = world.new_entity();
+    entity.position = gravity.inner.position + (radial * distance).round().cast();
+
+    let acceleration = Entity::gravity_acceleration(entity.position, gravity, &world.settings, world.size_field, &world.rng);
+    let pull = (-acceleration.dot(radial)).max(0.0);
+    entity.speed = Vector2D::new(-radial.y, radial.x) * (pull * distance).sqrt() + gravity.inner.speed;
+    entity
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lattice_and_gaussian_stay_inside_the_field() {
+        let mut world = crate::new_world();
+        world.max_particles = 20_000;
+        world.settings.generator_sigma = 1_000.0;
+        world.settings.generator_spacing = 100.0;
+        for generator in [Generator::Gaussian, Generator::Lattice] {
+            world.settings.generator = generator;
+            world.generate();
+            assert_eq!(world.entities.len(), 20_000);
+            assert!(world.entities.iter().all(|entity| inside(entity.position, world.size_field)), "{}", generator.name());
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,219 @@
+use crate::Coordinate;
+use crate::entity::Entity;
+use crate::threadpool::Threadpool;
+
+use euclid::default::{Point2D, Vector2D};
+
+// Cells are widened past the requested size when the covered area is too large for a dense grid
+const MAX_CELLS_AXIS: Coordinate = 1_024;
+
+#[derive(Default)]
+pub struct Grid {
+    cell_size: Coordinate,
+    origin: Point2D<Coordinate>,
+    extent: Vector2D<Coordinate>,
+    size: (i32, i32),
+    size_field: (Coordinate, Coordinate),
+    wrap: (bool, bool),
+    cells: Vec<u32>,
+    cell_start: Vec<u32>,
+    indices: Vec<u32>,
+}
+
+impl Grid {
+    pub fn cell_size(&self) -> Coordinate {
+        self.cell_size
+    }
+
+    pub fn num_cells(&self) -> usize {
+        (self.size.0 * self.size.1) as usize
+    }
+
+    pub fn max_occupancy(&self) -> u32 {
+        self.cell_start.windows(2).map(|cell| cell[1] - cell[0]).max().unwrap_or(0)
+    }
+
+    // Wrapped axes are split into whole cells at least cell_size wide, a narrower last cell would hide neighbours across the seam
+    #[allow(clippy::unnecessary_cast)]
+    fn cell_coordinate(value: Coordinate, origin: Coordinate, cell_size: Coordinate, extent: Coordinate, size: i32, wrap: bool) -> i32 {
+        let offset = value - origin;
+        let cell = if wrap { (offset as i128 * size as i128 / extent.max(1) as i128) as Coordinate } else { offset / cell_size };
+        cell.clamp(0, size as Coordinate - 1) as i32
+    }
+
+    fn cell_coordinates(&self, position: Point2D<Coordinate>) -> (i32, i32) {
+        (
+            Grid::cell_coordinate(position.x, self.origin.x, self.cell_size, self.extent.x, self.size.0, self.wrap.0),
+            Grid::cell_coordinate(position.y, self.origin.y, self.cell_size, self.extent.y, self.size.1, self.wrap.1),
+        )
+    }
+
+    // Wrapped axes cover the whole field, the others only the bounding box of the entities
+    fn bounds(entities: &[Entity], size_field: (Coordinate, Coordinate), wrap: (bool, bool), threadpool: &mut Threadpool) -> (Point2D<Coordinate>, Point2D<Coordinate>) {
+        let empty = (Point2D::new(Coordinate::MAX, Coordinate::MAX), Point2D::new(Coordinate::MIN, Coordinate::MIN));
+        let chunks = threadpool.map_chunks(entities.len(), |_chunk, range| {
+            entities[range].iter().fold(empty, |(min, max), entity| (min.min(entity.position), max.max(entity.position)))
+        });
+        let (mut min, mut max) = chunks.into_iter().fold(empty, |(min, max), (chunk_min, chunk_max)| (min.min(chunk_min), max.max(chunk_max)));
+        if entities.is_empty() { return (Point2D::new(0, 0), size_field.into()) }
+        if wrap.0 { min.x = 0; max.x = size_field.0; }
+        if wrap.1 { min.y = 0; max.y = size_field.1; }
+        (min, max)
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    pub fn build(&mut self, entities: &[Entity], size_field: (Coordinate, Coordinate), cell_size: Coordinate, wrap: (bool, bool), threadpool: &mut Threadpool) {
+        let (min, max) = Grid::bounds(entities, size_field, wrap, threadpool);
+        let extent = max - min;
+        let largest_side = extent.x.max(extent.y);
+        self.cell_size = cell_size.max((largest_side + MAX_CELLS_AXIS - 1) / MAX_CELLS_AXIS).max(1);
+        self.origin = min;
+        self.extent = extent;
+        let cells = |extent: Coordinate, wrap: bool| {
+            if wrap { extent / self.cell_size } else { extent / self.cell_size + 1 }
+        };
+        self.size = (cells(extent.x, wrap.0).max(1) as i32, cells(extent.y, wrap.1).max(1) as i32);
+        self.size_field = size_field;
+        self.wrap = wrap;
+        let num_cells = self.num_cells();
+
+        let mut cells = std::mem::take(&mut self.cells);
+        cells.resize(entities.len(), 0);
+        let grid = &*self;
+        threadpool.for_each_chunk_mut(&mut cells, |_chunk, offset, cells| {
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let (x, y) = grid.cell_coordinates(entities[offset + i].position);
+                *cell = (y * grid.size.0 + x) as u32;
+            }
+        });
+        self.cells = cells;
+
+        // Counting sort, each chunk counts then scatters its own entities
+        let cells = &self.cells;
+        let mut offsets = threadpool.map_chunks(entities.len(), |_chunk, range| {
+            let mut count = vec![0u32; num_cells];
+            for i in range {
+                count[cells[i] as usize] += 1;
+            }
+            count
+        });
+
+        self.cell_start.clear();
+        self.cell_start.resize(num_cells + 1, 0);
+        let mut total = 0;
+        for cell in 0..num_cells {
+            self.cell_start[cell] = total;
+            for count in offsets.iter_mut() {
+                let num = count[cell];
+                count[cell] = total;
+                total += num;
+            }
+        }
+        self.cell_start[num_cells] = total;
+
+        self.indices.resize(entities.len(), 0);
+        let indices = std::sync::atomic::AtomicPtr::new(self.indices.as_mut_ptr());
+        let offsets = &offsets;
+        threadpool.map_chunks(entities.len(), |chunk, range| {
+            let indices = indices.load(std::sync::atomic::Ordering::Relaxed);
+            let mut offset = offsets[chunk].clone();
+            for i in range {
+                let cell = cells[i] as usize;
+                unsafe { *indices.add(offset[cell] as usize) = i as u32; }
+                offset[cell] += 1;
+            }
+        });
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> &[u32] {
+        let cell = (y * self.size.0 + x) as usize;
+        &self.indices[self.cell_start[cell] as usize..self.cell_start[cell + 1] as usize]
+    }
+
+    pub fn direction(&self, from: Point2D<Coordinate>, to: Point2D<Coordinate>) -> Vector2D<Coordinate> {
+        let mut direction = to - from;
+        if self.wrap.0 {
+            if direction.x > self.size_field.0 / 2 { direction.x -= self.size_field.0; }
+            if direction.x < -self.size_field.0 / 2 { direction.x += self.size_field.0; }
+        }
+        if self.wrap.1 {
+            if direction.y > self.size_field.1 / 2 { direction.y -= self.size_field.1; }
+            if direction.y < -self.size_field.1 / 2 { direction.y += self.size_field.1; }
+        }
+        direction
+    }
+
+    // Calls f(index, direction to the neighbour, distance squared) for every entity within radius of position, itself included
+    pub fn for_each_neighbour<F: FnMut(usize, Vector2D<f32>, f32)>(&self, entities: &[Entity], position: Point2D<Coordinate>, radius: f32, mut f: F) {
+        if self.cell_start.is_empty() { return }
+
+        let (x, y) = self.cell_coordinates(position);
+        let reach = (radius / self.cell_size as f32).ceil() as i32;
+        let range_x = if self.wrap.0 { (-reach).max(-(self.size.0 - 1) / 2)..=reach.min(self.size.0 / 2) } else { (-reach).max(-x)..=reach.min(self.size.0 - 1 - x) };
+        let range_y = if self.wrap.1 { (-reach).max(-(self.size.1 - 1) / 2)..=reach.min(self.size.1 / 2) } else { (-reach).max(-y)..=reach.min(self.size.1 - 1 - y) };
+        let radius_squared = radius * radius;
+
+        for offset_y in range_y {
+            let cell_y = (y + offset_y).rem_euclid(self.size.1);
+            for offset_x in range_x.clone() {
+                let cell_x = (x + offset_x).rem_euclid(self.size.0);
+                for index in self.cell(cell_x, cell_y) {
+                    let direction = self.direction(position, entities[*index as usize].position).to_f32();
+                    let distance_squared = direction.square_length();
+                    if distance_squared <= radius_squared {
+                        f(*index as usize, direction, distance_squared);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every pair within the radius, through the seam on wrapped axes, as the grid has to report it
+    fn brute_force(entities: &[Entity], grid: &Grid, index: usize, radius: f32) -> Vec<usize> {
+        (0..entities.len()).filter(|other| grid.direction(entities[index].position, entities[*other].position).to_f32().length() <= radius).collect()
+    }
+
+    #[test]
+    fn neighbours_across_wrap_seam_match_brute_force() {
+        let mut threadpool = Threadpool::new_with_threads(2);
+        let rng = fastrand::Rng::with_seed(7);
+        // Neither side is a multiple of the cell size
+        let size_field = (1_005, 733);
+        let mut entities: Vec<Entity> = (0..2_000).map(|_| Entity::new_random(size_field, &rng)).collect();
+        entities.push(Entity { position: Point2D::new(1, 1), ..Entity::default() });
+        entities.push(Entity { position: Point2D::new(size_field.0 - 1, size_field.1 - 1), ..Entity::default() });
+
+        let mut grid = Grid::default();
+        for (cell_size, radius) in [(10, 10.0), (10, 25.0), (7, 7.0), (64, 40.0)] {
+            grid.build(&entities, size_field, cell_size, (true, true), &mut threadpool);
+            for index in 0..entities.len() {
+                let mut found = Vec::new();
+                grid.for_each_neighbour(&entities, entities[index].position, radius, |other, _, _| found.push(other));
+                found.sort_unstable();
+                assert_eq!(found, brute_force(&entities, &grid, index, radius), "cell_size {} radius {} index {}", cell_size, radius, index);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_inside_blocked_field_match_brute_force() {
+        let mut threadpool = Threadpool::new_with_threads(2);
+        let rng = fastrand::Rng::with_seed(11);
+        let size_field = (1_005, 733);
+        let entities: Vec<Entity> = (0..2_000).map(|_| Entity::new_random(size_field, &rng)).collect();
+
+        let mut grid = Grid::default();
+        grid.build(&entities, size_field, 10, (false, false), &mut threadpool);
+        for index in 0..entities.len() {
+            let mut found = Vec::new();
+            grid.for_each_neighbour(&entities, entities[index].position, 25.0, |other, _, _| found.push(other));
+            found.sort_unstable();
+            assert_eq!(found, brute_force(&entities, &grid, index, 25.0));
+        }
+    }
+}
\ No newline at end of file
@@ -1,13 +1,21 @@
-use crate::{PRECISION, SIZE_X, SIZE_Y};
-use crate::entity::{GravityCompute, GravityEntity};
-use crate::world::{Settings, World};
+use crate::{Coordinate, PRECISION, random_coordinate};
+use crate::bond;
+use crate::diagnostics;
+use crate::emitter::{Emitter, Sink};
+use crate::entity::{GravityCompute, GravityEntity, GravitySettings, Integrator, Thermostat};
+use crate::force_field::ForceField;
+use crate::generator::Generator;
+use crate::obstacle::Obstacle;
+use crate::world::{Backend, Boundary, ChargeSpawn, ColorMode, Interaction, MAX_SPECIES, Settings, SpawnRule, Timestep, World};
 
 use euclid::default::{Point2D, Vector2D};
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct GUI {
     pub imgui: imgui::Context,
     pub imgui_winit_platform: imgui_winit_support::WinitPlatform,
-    mouse_on_gui: bool
+    mouse_on_gui: bool,
+    export_status: String,
 }
 
 impl GUI {
@@ -22,9 +30,10 @@ impl GUI {
         imgui_winit_platform.attach_window(imgui_io, window, imgui_winit_support::HiDpiMode::Default);
 
         GUI {
-            imgui: imgui,
-            imgui_winit_platform: imgui_winit_platform,
+            imgui,
+            imgui_winit_platform,
             mouse_on_gui: false,
+            export_status: String::new(),
         }
     }
 
@@ -38,28 +47,149 @@ impl GUI {
             .movable(false)
             .resizable(false)
             .position([0.0, 0.0], imgui::Condition::Always)
-            .size([400.0 as f32, window_size.height as f32], imgui::Condition::Always)
+            .size([400.0, window_size.height as f32], imgui::Condition::Always)
             .scrollable(true)
             .bg_alpha(0.5)
             .build(&ui, || {
-                let mut force_mouse = world.settings.mouse_gravity.gravity.compute.get_force();
-                let mouse_gravity_mode_str = match world.settings.mouse_gravity.gravity.compute { GravityCompute::Linear(_) => { "linear" }, GravityCompute::Square(_) => { "squared" } };
-                imgui::ComboBox::new("mouse_gravity_mode").preview_value(mouse_gravity_mode_str).build(&ui, || {
-                    if imgui::Selectable::new("linear").build(&ui) { world.settings.mouse_gravity.gravity.compute = GravityCompute::Linear(force_mouse); }
-                    if imgui::Selectable::new("squared").build(&ui) { world.settings.mouse_gravity.gravity.compute = GravityCompute::Square(force_mouse); }
-                });
-                imgui::Slider::new("force_mouse", -1_000.0, 1_000.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut force_mouse);
-                world.settings.mouse_gravity.gravity.compute.set_force(force_mouse);
+                gravity_settings_ui(&ui, "mouse_gravity_mode", "force_mouse", "", 1_000.0, &mut world.settings.mouse_gravity.gravity);
+                imgui::Slider::new("charge_mouse", -10.0, 10.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.mouse_gravity.inner.charge);
 
                 ui.separator();
 
                 imgui::Slider::new("max_speed", 1.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.max_speed);
                 imgui::Slider::new("max_gravity_speed", 1.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.max_gravity_speed);
                 imgui::Slider::new("energy_loss", 0.0, 10.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.energy_loss);
+                imgui::ComboBox::new("thermostat").preview_value(world.settings.thermostat.name()).build(&ui, || {
+                    for thermostat in Thermostat::ALL {
+                        if imgui::Selectable::new(thermostat.name()).build(&ui) { world.settings.thermostat = thermostat; }
+                    }
+                });
+                if world.settings.thermostat != Thermostat::EnergyLoss {
+                    imgui::Slider::new("temperature", 0.0, 1_000_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.temperature);
+                    imgui::Slider::new("thermostat_coupling", 0.01, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.thermostat_coupling);
+                }
                 imgui::Slider::new("gravity_power", 0.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.gravity_power);
-                ui.checkbox("block", &mut world.settings.block);
+                let window_field = (window_size.width as Coordinate, window_size.height as Coordinate);
+                if imgui::Slider::new("field_scale", 1.0, World::max_field_scale(window_field)).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.field_scale) {
+                    world.resize(window_field);
+                }
+                imgui::Slider::new("zoom", 0.01 * PRECISION as f32, 0.5 * PRECISION as f32 * world.settings.field_scale).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.zoom);
+                point_ui(&ui, "camera".to_string(), &mut world.position_camera);
+                imgui::ComboBox::new("boundary_x").preview_value(world.settings.boundary_x.name()).build(&ui, || {
+                    for boundary in Boundary::ALL {
+                        if imgui::Selectable::new(boundary.name().to_string() + "##x").build(&ui) { world.settings.boundary_x = boundary; }
+                    }
+                });
+                imgui::ComboBox::new("boundary_y").preview_value(world.settings.boundary_y.name()).build(&ui, || {
+                    for boundary in Boundary::ALL {
+                        if imgui::Selectable::new(boundary.name().to_string() + "##y").build(&ui) { world.settings.boundary_y = boundary; }
+                    }
+                });
+                if world.settings.boundary_x == Boundary::Absorb || world.settings.boundary_y == Boundary::Absorb {
+                    imgui::ComboBox::new("spawn_rule").preview_value(world.settings.spawn_rule.name()).build(&ui, || {
+                        for spawn_rule in SpawnRule::ALL {
+                            if imgui::Selectable::new(spawn_rule.name()).build(&ui) { world.settings.spawn_rule = spawn_rule; }
+                        }
+                    });
+                }
+                if world.settings.boundary_x == Boundary::Soft || world.settings.boundary_y == Boundary::Soft {
+                    imgui::Slider::new("wall_restitution", 0.0, 1.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.wall_restitution);
+                    imgui::Slider::new("wall_margin", 0.0, 200.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.wall_margin);
+                    imgui::Slider::new("wall_repulsion", 0.0, 100_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.wall_repulsion);
+                }
+                ui.checkbox("collisions", &mut world.settings.collisions);
+                if world.settings.collisions {
+                    imgui::Slider::new("particle_radius", 0.1, 20.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.particle_radius);
+                    imgui::Slider::new("restitution", 0.0, 1.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.restitution);
+                }
                 imgui::Slider::new("time_factor", 0.1, 10.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.time_factor);
+                imgui::ComboBox::new("timestep").preview_value(world.settings.timestep.name()).build(&ui, || {
+                    for timestep in [Timestep::Variable, Timestep::Fixed, Timestep::Adaptive] {
+                        if imgui::Selectable::new(timestep.name()).build(&ui) { world.settings.timestep = timestep; }
+                    }
+                });
+                imgui::ComboBox::new("integrator").preview_value(world.settings.integrator.name()).build(&ui, || {
+                    for integrator in Integrator::ALL {
+                        if imgui::Selectable::new(integrator.name()).build(&ui) { world.settings.integrator = integrator; }
+                    }
+                });
+                imgui::ComboBox::new("interaction").preview_value(world.settings.interaction.name()).build(&ui, || {
+                    for interaction in Interaction::ALL {
+                        if imgui::Selectable::new(interaction.name()).build(&ui) {
+                            world.settings.interaction = interaction;
+                            if interaction == Interaction::ParticleLife { world.settings.color_mode = ColorMode::Species; }
+                        }
+                    }
+                });
+                if world.settings.interaction == Interaction::BarnesHut {
+                    imgui::Slider::new("nbody_gravity", 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.nbody_gravity);
+                    imgui::Slider::new("theta", 0.0, 1.5).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.theta);
+                    imgui::Slider::new("softening", 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.softening);
+                }
+                if world.settings.interaction == Interaction::Sph {
+                    imgui::Slider::new("smoothing_length", 1.0, 50.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.sph_smoothing_length);
+                    imgui::Slider::new("rest_density", 0.01, 10.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.rest_density);
+                    imgui::Slider::new("stiffness", 1.0, 100_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.stiffness);
+                    imgui::Slider::new("viscosity", 0.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.viscosity);
+                }
+                if world.settings.interaction == Interaction::Boids {
+                    imgui::Slider::new("perception_radius", 1.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.perception_radius);
+                    imgui::Slider::new("separation", 0.0, 100_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.separation);
+                    imgui::Slider::new("alignment", 0.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.alignment);
+                    imgui::Slider::new("cohesion", 0.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.cohesion);
+                    imgui::Slider::new("boids_speed", 0.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.boids_speed);
+                }
+                if world.settings.interaction == Interaction::ParticleLife {
+                    imgui::Slider::new("life_radius", 1.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.life_radius);
+                    imgui::Slider::new("life_strength", 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.life_strength);
+                }
+                if ui.collapsing_header("mass_charge", imgui::TreeNodeFlags::empty()) {
+                    imgui::Slider::new("mass_min", 0.01, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.mass_min);
+                    imgui::Slider::new("mass_max", 0.01, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.mass_max);
+                    imgui::ComboBox::new("charge_spawn").preview_value(world.settings.charge_spawn.name()).build(&ui, || {
+                        for charge_spawn in ChargeSpawn::ALL {
+                            if imgui::Selectable::new(charge_spawn.name()).build(&ui) { world.settings.charge_spawn = charge_spawn; }
+                        }
+                    });
+                    imgui::Slider::new("charge_magnitude", 0.0, 10.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.charge_magnitude);
+                    imgui::Slider::new("charge_strength", 0.0, 100_000_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.charge_strength);
+                    imgui::Slider::new("charge_softening", 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.charge_softening);
+                }
+                if ui.collapsing_header("species", imgui::TreeNodeFlags::empty()) {
+                    imgui::Slider::new("species_count", 1, MAX_SPECIES as u32).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.species_count);
+                    let species_count = world.settings.species_count as usize;
+                    for i in 0..species_count {
+                        let id_str = i.to_string();
+                        let (r, g, b) = world.settings.species_colors[i];
+                        let mut color = [r, g, b];
+                        imgui::ColorEdit::new("##color".to_string() + &id_str, &mut color).inputs(false).build(&ui); ui.same_line();
+                        world.settings.species_colors[i] = (color[0], color[1], color[2]);
+                        ui.set_next_item_width(80.0);
+                        imgui::Slider::new("##proportion".to_string() + &id_str, 0.0, 1.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.species_proportions[i]);
+                        for j in 0..species_count {
+                            ui.same_line();
+                            ui.set_next_item_width(40.0);
+                            imgui::Drag::new("##matrix".to_string() + &id_str + "_" + &j.to_string()).range(-1.0, 1.0).speed(0.01).display_format("%.2f").build(&ui, &mut world.settings.species_matrix[i][j]);
+                        }
+                    }
+                    if ui.button("randomize_matrix") { world.settings.randomize_species_matrix(&world.rng); }
+                }
+                if world.settings.timestep == Timestep::Fixed {
+                    imgui::Slider::new("fixed_timestep", 0.001, 0.05).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.fixed_timestep);
+                    imgui::Slider::new("max_substeps", 1, 64).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.max_substeps);
+                }
+                if world.settings.timestep == Timestep::Adaptive {
+                    imgui::Slider::new("adaptive_safety", 0.01, 1.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.adaptive_safety);
+                    imgui::Slider::new("adaptive_length", 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.adaptive_length);
+                    imgui::Slider::new("max_adaptive_steps", 1, 1024).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.max_adaptive_steps);
+                    ui.text("adaptive_steps = ".to_string() + &world.substeps.to_string());
+                }
 
+                imgui::ComboBox::new("color_mode").preview_value(world.settings.color_mode.name()).build(&ui, || {
+                    for color_mode in ColorMode::ALL {
+                        if imgui::Selectable::new(color_mode.name()).build(&ui) { world.settings.color_mode = color_mode; }
+                    }
+                });
                 let mut color_base = [world.settings.color_base.0, world.settings.color_base.1, world.settings.color_base.2];
                 let mut color_fast = [world.settings.color_fast.0, world.settings.color_fast.1, world.settings.color_fast.2];
                 imgui::ColorEdit::new("color_base", &mut color_base).build(&ui);
@@ -72,13 +202,49 @@ impl GUI {
 
                 ui.separator();
                 
-                ui.text("num_particles = ".to_string() + &world.num_particles.to_string()); ui.same_line();
-                if ui.button("x2") { world.num_particles *= 2; world.apply_particles_number(); } ui.same_line();
-                if ui.button("/2") { world.num_particles /= 2; world.num_particles = world.num_particles.max(1); world.apply_particles_number(); }
+                ui.text("max_particles = ".to_string() + &world.max_particles.to_string()); ui.same_line();
+                if ui.button("x2") { world.max_particles *= 2; world.apply_particles_cap(); } ui.same_line();
+                if ui.button("/2") { world.max_particles /= 2; world.max_particles = world.max_particles.max(1); world.truncate_particles(); }
+                ui.text("live = ".to_string() + &world.entities.len().to_string() + "  spawned = " + &world.spawned.to_string() + "  absorbed = " + &world.absorbed.to_string() + "  expired = " + &world.expired.to_string());
+                if ui.button("clear_particles") { world.edit_entities(); world.entities.clear(); world.bonds.clear(); }
 
                 ui.separator();
 
+                let mut seed = world.settings.seed.to_string();
+                if ui.input_text("seed", &mut seed).chars_decimal(true).enter_returns_true(true).build() {
+                    if let Ok(seed) = seed.parse() { world.settings.seed = seed; world.reset(); }
+                }
+                imgui::ComboBox::new("generator").preview_value(world.settings.generator.name()).build(&ui, || {
+                    for generator in Generator::ALL {
+                        if imgui::Selectable::new(generator.name()).build(&ui) { world.settings.generator = generator; }
+                    }
+                });
+                match world.settings.generator {
+                    Generator::Uniform => {}
+                    Generator::Disc | Generator::Rings | Generator::Galaxies => {
+                        if world.settings.generator != Generator::Galaxies {
+                            imgui::Slider::new("orbit_gravity_entity", 0, world.entities_gravity.len().max(1) as u32 - 1).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_gravity);
+                        }
+                        imgui::Slider::new("radius", 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_radius);
+                        imgui::Slider::new("inner_radius", 0.0, 1_000.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_inner_radius);
+                        if world.settings.generator == Generator::Rings {
+                            imgui::Slider::new("rings", 1, 50).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_rings);
+                        }
+                        if world.settings.generator == Generator::Galaxies {
+                            imgui::Slider::new("separation", 0.0, 2_000.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_separation);
+                            imgui::Slider::new("approach_speed", 0.0, 1_000.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_speed);
+                        }
+                    }
+                    Generator::Gaussian => {
+                        imgui::Slider::new("sigma", 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_sigma);
+                    }
+                    Generator::Lattice => {
+                        imgui::Slider::new("spacing", 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.generator_spacing);
+                    }
+                }
+                if ui.button("generate") { world.generate(); } ui.same_line();
                 if ui.button("reset_entities") { world.reset(); } ui.same_line();
+                if ui.button("random_seed") { world.settings.seed = fastrand::u64(..); world.reset(); } ui.same_line();
                 if ui.button("reset_settings") { world.settings = Settings::default(); }
 
                 ui.separator();
@@ -88,10 +254,22 @@ impl GUI {
 
                 ui.separator();
 
+                ui.checkbox("merging", &mut world.settings.merging); ui.same_line();
+                ui.checkbox("accretion", &mut world.settings.accretion);
+                if world.settings.merging || world.settings.accretion {
+                    imgui::Slider::new("capture_radius", 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.capture_radius);
+                }
+                if world.settings.accretion {
+                    imgui::Slider::new("accretion_force", 0.000_001, 1.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.accretion_force);
+                }
+                ui.checkbox("coupling", &mut world.settings.coupling);
+                if world.settings.coupling {
+                    imgui::Slider::new("coupling_mass_ratio", 0.000_000_1, 0.01).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.coupling_mass_ratio);
+                }
                 if ui.button("new gravity_entity") {
                     let position = Point2D::new(
-                        fastrand::i32((0)..(SIZE_X * PRECISION)),
-                        fastrand::i32((0)..(SIZE_Y * PRECISION)),
+                        random_coordinate(&world.rng, 0..world.size_field.0),
+                        random_coordinate(&world.rng, 0..world.size_field.1),
                     );
                     let mut new_entity = GravityEntity::default();
                     new_entity.inner.position = position;
@@ -107,14 +285,8 @@ impl GUI {
                         imgui::TreeNode::new(index.to_string()).flags(imgui::TreeNodeFlags::DEFAULT_OPEN).build(&ui, || {
                             let id_str = index.to_string();
                             ui.indent();
-                            let mut force = gravity_entity.gravity.compute.get_force();
-                            let gravity_mode_str = match gravity_entity.gravity.compute { GravityCompute::Linear(_) => { "linear".to_string() }, GravityCompute::Square(_) => { "squared".to_string() } };
-                            imgui::ComboBox::new("mouse_gravity_mode").preview_value(gravity_mode_str + "##" + &id_str).build(&ui, || {
-                                if imgui::Selectable::new("linear##".to_string() + &id_str).build(&ui) { gravity_entity.gravity.compute = GravityCompute::Linear(force_mouse); }
-                                if imgui::Selectable::new("squared##".to_string() + &id_str).build(&ui) { gravity_entity.gravity.compute = GravityCompute::Square(force_mouse); }
-                            });
-                            imgui::Slider::new("gravity_force##".to_string() + &id_str, -100.0, 100.0).build(&ui, &mut force);
-                            gravity_entity.gravity.compute.set_force(force);
+                            gravity_settings_ui(&ui, "gravity_mode", "gravity_force", &id_str, 100.0, &mut gravity_entity.gravity);
+                            imgui::Slider::new("charge##".to_string() + &id_str, -10.0, 10.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut gravity_entity.inner.charge);
 
                             ui.checkbox("movable##".to_string() + &id_str, &mut gravity_entity.movable);
                             if !gravity_entity.movable { gravity_entity.inner.speed = Vector2D::zero(); }
@@ -127,26 +299,261 @@ impl GUI {
                     to_delete.iter().rev().for_each(|i| { world.entities_gravity.remove(*i); });
                 }
 
+                ui.separator();
+
+                for (index, name) in ForceField::NAMES.iter().enumerate() {
+                    if index > 0 { ui.same_line(); }
+                    if ui.button("new ".to_string() + name) {
+                        let new_field = ForceField::new(index, world.position_camera, &world.rng);
+                        world.force_fields.push(new_field);
+                    }
+                }
+
+                if ui.collapsing_header("force_fields", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+                    let mut to_delete = Vec::new();
+                    for (index, field) in world.force_fields.iter_mut().enumerate() {
+                        ui.indent();
+                        imgui::TreeNode::new("field_".to_string() + &index.to_string()).label::<String, String>(field.name().to_string() + " " + &index.to_string()).flags(imgui::TreeNodeFlags::DEFAULT_OPEN).build(&ui, || {
+                            let id_str = "field_".to_string() + &index.to_string();
+                            ui.indent();
+                            match field {
+                                ForceField::Line { start, end, gravity } => {
+                                    point_ui(&ui, "start##".to_string() + &id_str, start);
+                                    point_ui(&ui, "end##".to_string() + &id_str, end);
+                                    gravity_settings_ui(&ui, "gravity_mode", "gravity_force", &id_str, 100.0, gravity);
+                                }
+                                ForceField::Ring { center, radius, gravity } => {
+                                    point_ui(&ui, "center##".to_string() + &id_str, center);
+                                    imgui::Slider::new("radius##".to_string() + &id_str, 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, radius);
+                                    gravity_settings_ui(&ui, "gravity_mode", "gravity_force", &id_str, 100.0, gravity);
+                                }
+                                ForceField::Wind { min, max, angle, force } => {
+                                    point_ui(&ui, "min##".to_string() + &id_str, min);
+                                    point_ui(&ui, "max##".to_string() + &id_str, max);
+                                    imgui::AngleSlider::new("angle##".to_string() + &id_str).min_degrees(-180.0).max_degrees(180.0).build(&ui, angle);
+                                    imgui::Slider::new("wind_force##".to_string() + &id_str, 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, force);
+                                }
+                                ForceField::CurlNoise { scale, speed, force, .. } => {
+                                    imgui::Slider::new("scale##".to_string() + &id_str, 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, scale);
+                                    imgui::Slider::new("speed##".to_string() + &id_str, 0.0, 10.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, speed);
+                                    imgui::Slider::new("flow_force##".to_string() + &id_str, 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, force);
+                                }
+                            }
+                            if ui.button("delete##".to_string() + &id_str) { to_delete.push(index); }
+                            ui.unindent();
+                        });
+                        ui.unindent();
+                    }
+                    to_delete.iter().rev().for_each(|i| { world.force_fields.remove(*i); });
+                }
+
+                ui.separator();
+
+                for (index, name) in Obstacle::NAMES.iter().enumerate() {
+                    if index > 0 { ui.same_line(); }
+                    if ui.button("new ".to_string() + name) { world.obstacles.push(Obstacle::new(index, world.position_camera)); }
+                }
+                imgui::Slider::new("obstacle_restitution", 0.0, 1.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.obstacle_restitution);
+                imgui::Slider::new("obstacle_friction", 0.0, 1.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.obstacle_friction);
+
+                if ui.collapsing_header("obstacles", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+                    let mut to_delete = Vec::new();
+                    for (index, obstacle) in world.obstacles.iter_mut().enumerate() {
+                        ui.indent();
+                        imgui::TreeNode::new("obstacle_".to_string() + &index.to_string()).label::<String, String>(obstacle.name().to_string() + " " + &index.to_string()).flags(imgui::TreeNodeFlags::DEFAULT_OPEN).build(&ui, || {
+                            let id_str = "obstacle_".to_string() + &index.to_string();
+                            ui.indent();
+                            match obstacle {
+                                Obstacle::Circle { center, radius } => {
+                                    point_ui(&ui, "center##".to_string() + &id_str, center);
+                                    imgui::Slider::new("radius##".to_string() + &id_str, 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, radius);
+                                }
+                                Obstacle::Box { min, max } => {
+                                    point_ui(&ui, "min##".to_string() + &id_str, min);
+                                    point_ui(&ui, "max##".to_string() + &id_str, max);
+                                }
+                                Obstacle::Polygon { vertices } => {
+                                    ui.text("convex vertices");
+                                    for (i, vertex) in vertices.iter_mut().enumerate() {
+                                        point_ui(&ui, i.to_string() + "##" + &id_str, vertex);
+                                    }
+                                    if ui.button("add_vertex##".to_string() + &id_str) {
+                                        let (first, last) = (vertices[0], vertices[vertices.len() - 1]);
+                                        vertices.push(first.to_f32().lerp(last.to_f32(), 0.5).cast());
+                                    }
+                                    ui.same_line();
+                                    if ui.button("remove_vertex##".to_string() + &id_str) && vertices.len() > 3 { vertices.pop(); }
+                                }
+                            }
+                            if ui.button("delete##".to_string() + &id_str) { to_delete.push(index); }
+                            ui.unindent();
+                        });
+                        ui.unindent();
+                    }
+                    to_delete.iter().rev().for_each(|i| { world.obstacles.remove(*i); });
+                }
+
+                ui.separator();
+
+                for (index, shape) in bond::Shape::ALL.iter().enumerate() {
+                    if index > 0 { ui.same_line(); }
+                    if ui.button("new ".to_string() + shape.name()) { bond::spawn(world, *shape, world.position_camera); }
+                }
+                imgui::Slider::new("bond_size", 2, 64).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.bond_size);
+                imgui::Slider::new("bond_spacing", 1.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.bond_spacing);
+                imgui::Slider::new("bond_stiffness", 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.bond_stiffness);
+                imgui::Slider::new("bond_damping", 0.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.bond_damping);
+                imgui::Slider::new("bond_break_strain", 0.0, 10.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.bond_break_strain);
+                ui.text("bonds = ".to_string() + &world.bonds.len().to_string() + "  broken = " + &world.broken_bonds.to_string());
+                if ui.button("clear_bonds") { world.bonds.clear(); }
+
+                ui.separator();
+
+                if ui.button("new emitter") { world.emitters.push(Emitter::new(world.position_camera)); } ui.same_line();
+                if ui.button("new sink") { world.sinks.push(Sink::new(world.position_camera)); }
+
+                if ui.collapsing_header("emitters", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+                    let mut to_delete = Vec::new();
+                    for (index, emitter) in world.emitters.iter_mut().enumerate() {
+                        ui.indent();
+                        imgui::TreeNode::new("emitter_".to_string() + &index.to_string()).label::<String, String>("emitter ".to_string() + &index.to_string()).flags(imgui::TreeNodeFlags::DEFAULT_OPEN).build(&ui, || {
+                            let id_str = "emitter_".to_string() + &index.to_string();
+                            ui.indent();
+                            point_ui(&ui, "position##".to_string() + &id_str, &mut emitter.position);
+                            imgui::Slider::new("rate##".to_string() + &id_str, 1.0, 1_000_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut emitter.rate);
+                            imgui::Slider::new("spread##".to_string() + &id_str, 0.0, 500.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut emitter.spread);
+                            imgui::AngleSlider::new("direction##".to_string() + &id_str).min_degrees(-180.0).max_degrees(180.0).build(&ui, &mut emitter.direction);
+                            imgui::AngleSlider::new("cone##".to_string() + &id_str).min_degrees(0.0).max_degrees(180.0).build(&ui, &mut emitter.cone);
+                            imgui::Slider::new("speed##".to_string() + &id_str, 0.0, 10_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut emitter.speed);
+                            imgui::Slider::new("lifetime##".to_string() + &id_str, 0.0, 100.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut emitter.lifetime);
+                            if ui.button("delete##".to_string() + &id_str) { to_delete.push(index); }
+                            ui.unindent();
+                        });
+                        ui.unindent();
+                    }
+                    to_delete.iter().rev().for_each(|i| { world.emitters.remove(*i); });
+                }
+
+                if ui.collapsing_header("sinks", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+                    let mut to_delete = Vec::new();
+                    for (index, sink) in world.sinks.iter_mut().enumerate() {
+                        ui.indent();
+                        imgui::TreeNode::new("sink_".to_string() + &index.to_string()).label::<String, String>("sink ".to_string() + &index.to_string()).flags(imgui::TreeNodeFlags::DEFAULT_OPEN).build(&ui, || {
+                            let id_str = "sink_".to_string() + &index.to_string();
+                            ui.indent();
+                            point_ui(&ui, "position##".to_string() + &id_str, &mut sink.position);
+                            imgui::Slider::new("radius##".to_string() + &id_str, 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut sink.radius);
+                            if ui.button("delete##".to_string() + &id_str) { to_delete.push(index); }
+                            ui.unindent();
+                        });
+                        ui.unindent();
+                    }
+                    to_delete.iter().rev().for_each(|i| { world.sinks.remove(*i); });
+                }
+
                 ui.separator();
                 if ui.collapsing_header("benchmark", imgui::TreeNodeFlags::DEFAULT_OPEN) {
                     ui.text("update_time = ".to_string() + &(world.benchmark_update * 1_000.0).to_string() + "ms");
                     ui.text("draw_time   = ".to_string() + &(world.benchmark_draw * 1_000.0).to_string() + "ms");
+                    ui.text("substeps    = ".to_string() + &world.substeps.to_string());
+                    ui.checkbox("simd", &mut world.settings.simd);
+                    imgui::ComboBox::new("backend").preview_value(world.settings.backend.name()).build(&ui, || {
+                        for backend in Backend::ALL {
+                            if imgui::Selectable::new(backend.name()).build(&ui) { world.settings.backend = backend; }
+                        }
+                    });
+                    if world.settings.backend == Backend::Gpu {
+                        if world.gpu_active() {
+                            ui.text("gpu: semi implicit euler with gravity, charges, walls, boundaries and energy_loss, diagnostics paused");
+                        } else if let Some(feature) = world.gpu_unsupported() {
+                            ui.text("gpu: ".to_string() + feature + " needs the cpu step, running on the cpu");
+                        } else {
+                            ui.text("gpu: unavailable for this field size, running on the cpu");
+                        }
+                    }
+                    if world.debug {
+                        imgui::Slider::new("grid_cell_size", 1.0, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.grid_cell_size);
+                        ui.text("grid_cells  = ".to_string() + &world.grid.num_cells().to_string());
+                        ui.text("max_in_cell = ".to_string() + &world.grid.max_occupancy().to_string());
+                    }
+                }
+                ui.separator();
+                if ui.collapsing_header("diagnostics", imgui::TreeNodeFlags::empty()) {
+                    ui.checkbox("diagnostics", &mut world.settings.diagnostics);
+                    imgui::Slider::new("history", 10, 100_000).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(&ui, &mut world.settings.diagnostics_history);
+                    let history = &world.diagnostics_history;
+                    let plot = |label: &str, value: &dyn Fn(&diagnostics::Diagnostics) -> f32| {
+                        let values: Vec<f32> = history.iter().map(value).collect();
+                        let current = values.last().copied().unwrap_or(0.0);
+                        ui.plot_lines(label, &values).overlay_text(current.to_string()).graph_size([0.0, 50.0]).build();
+                    };
+                    plot("kinetic", &|d| d.kinetic);
+                    plot("potential", &|d| d.potential);
+                    plot("total", &|d| d.total());
+                    plot("momentum_x", &|d| d.momentum.x);
+                    plot("momentum_y", &|d| d.momentum.y);
+                    plot("angular_momentum", &|d| d.angular_momentum);
+                    plot("dispersion", &|d| d.dispersion);
+                    plot("temperature", &|d| d.temperature);
+                    if ui.button("export_csv") {
+                        self.export_status = match diagnostics::export_csv(&world.diagnostics_history, "diagnostics.csv") {
+                            Ok(()) => { "saved diagnostics.csv".to_string() }
+                            Err(error) => { error.to_string() }
+                        };
+                    }
+                    if !self.export_status.is_empty() { ui.same_line(); ui.text(&self.export_status); }
                 }
                 ui.separator();
 
                 ui.text("num_threads = ".to_string() + &world.threadpool.num_threads().to_string()); ui.same_line();
                 if ui.button("+1") { world.threadpool.new_thread(); } ui.same_line();
-                if ui.button("-1") { if world.threadpool.num_threads() > 1 { world.threadpool.delete_thread(); } }
+                if ui.button("-1") && world.threadpool.num_threads() > 1 { world.threadpool.delete_thread(); }
             }
         );
 
-        if ui.is_any_item_hovered() || ui.is_window_hovered_with_flags(imgui::WindowHoveredFlags::ANY_WINDOW) { self.mouse_on_gui = true; }
-        else { self.mouse_on_gui = false; }
+        self.mouse_on_gui = ui.is_any_item_hovered() || ui.is_window_hovered_with_flags(imgui::WindowHoveredFlags::ANY_WINDOW);
 
         // Draw
         self.imgui_winit_platform.prepare_render(&ui, window);
 
-        return ui.render();
-        
+        ui.render()
+    }
+}
+
+fn gravity_settings_ui(ui: &imgui::Ui, mode_label: &str, force_label: &str, id_str: &str, max_force: f32, gravity: &mut GravitySettings) {
+    let id = "##".to_string() + id_str;
+    let mut force = gravity.compute.get_force();
+    imgui::ComboBox::new(mode_label.to_string() + &id).preview_value(gravity.compute.name()).build(ui, || {
+        for (index, name) in GravityCompute::NAMES.iter().enumerate() {
+            if imgui::Selectable::new(name.to_string() + &id).build(ui) { gravity.compute = GravityCompute::from_index(index, force); }
+        }
+    });
+    imgui::Slider::new(force_label.to_string() + &id, -max_force, max_force).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(ui, &mut force);
+    gravity.compute.set_force(force);
+
+    match &mut gravity.compute {
+        GravityCompute::Softened { softening, .. } => { imgui::Slider::new("softening".to_string() + &id, 0.1, 100.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(ui, softening); }
+        GravityCompute::Spring { rest_length, .. } => { imgui::Slider::new("rest_length".to_string() + &id, 0.0, 1_000.0).flags(imgui::SliderFlags::ALWAYS_CLAMP).build(ui, rest_length); }
+        GravityCompute::Gaussian { width, .. } => { imgui::Slider::new("width".to_string() + &id, 1.0, 1_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(ui, width); }
+        _ => {}
+    }
+
+    let mut use_cutoff = gravity.cutoff.is_some();
+    ui.checkbox("cutoff".to_string() + &id, &mut use_cutoff);
+    if use_cutoff {
+        let mut cutoff = gravity.cutoff.unwrap_or(200.0);
+        ui.same_line();
+        imgui::Slider::new("cutoff_radius".to_string() + &id, 1.0, 2_000.0).flags(imgui::SliderFlags::LOGARITHMIC | imgui::SliderFlags::ALWAYS_CLAMP).build(ui, &mut cutoff);
+        gravity.cutoff = Some(cutoff);
+    } else {
+        gravity.cutoff = None;
+    }
+}
+
+// Only written back when edited, f64 keeps large_world coordinates exact at the drag resolution
+fn point_ui(ui: &imgui::Ui, label: String, point: &mut Point2D<Coordinate>) {
+    let mut values = [point.x as f64 / PRECISION as f64, point.y as f64 / PRECISION as f64];
+    if imgui::Drag::new(label).speed(1.0).build_array(ui, &mut values) {
+        *point = Point2D::new((values[0] * PRECISION as f64).round() as Coordinate, (values[1] * PRECISION as f64).round() as Coordinate);
     }
 }
\ No newline at end of file
@@ -1,7 +1,22 @@
 #![allow(unused_unsafe)]
 
+mod benchmark;
+mod boids;
+mod bond;
+mod collision;
+mod compute;
+mod diagnostics;
+mod emitter;
+mod force_field;
+mod generator;
 pub mod entity;
+pub mod grid;
 mod gui;
+mod obstacle;
+mod particle_life;
+mod quadtree;
+mod soa;
+mod sph;
 mod threadpool;
 pub mod world;
 
@@ -14,11 +29,102 @@ use world::World;
 
 use gui::GUI;
 
-const PRECISION: i32 = 1_000;
+// Fixed point coordinates, i64 with the large_world feature for fields far larger than the window
+#[cfg(not(feature = "large_world"))]
+pub type Coordinate = i32;
+#[cfg(feature = "large_world")]
+pub type Coordinate = i64;
+
+// Units per pixel, overridable at build time with the DUST_PRECISION environment variable
+const PRECISION: Coordinate = match option_env!("DUST_PRECISION") {
+    Some(precision) => { parse_precision(precision) }
+    None => { 1_000 }
+};
+
+#[cfg(not(feature = "large_world"))]
+pub fn random_coordinate(rng: &fastrand::Rng, range: std::ops::Range<Coordinate>) -> Coordinate {
+    rng.i32(range)
+}
+
+#[cfg(feature = "large_world")]
+pub fn random_coordinate(rng: &fastrand::Rng, range: std::ops::Range<Coordinate>) -> Coordinate {
+    rng.i64(range)
+}
+
+const fn parse_precision(text: &str) -> Coordinate {
+    let bytes = text.as_bytes();
+    let mut value: Coordinate = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "DUST_PRECISION must be a positive integer");
+        value = value * 10 + (bytes[i] - b'0') as Coordinate;
+        i += 1;
+    }
+    assert!(value > 0, "DUST_PRECISION must be a positive integer");
+    value
+}
+
 const SIZE_X: i32 = 1280;
 const SIZE_Y: i32 = 720;
 
+fn new_world() -> Box<World> {
+    Box::new(World {
+        position_camera: Point2D::new(SIZE_X as Coordinate * PRECISION / 2, SIZE_Y as Coordinate * PRECISION / 2),
+        size_field: (SIZE_X as Coordinate * PRECISION, SIZE_Y as Coordinate * PRECISION),
+        zoom: 0.5 * PRECISION as f32,
+        debug: false,
+        last_update: std::time::Instant::now(),
+        elapsed_since_update: 1.0,
+        accumulator: 0.0,
+        interpolation: 1.0,
+        substeps: 0,
+        entities: Vec::new(),
+        vertices: Vec::new(),
+        entities_gravity: Vec::new(),
+        force_fields: Vec::new(),
+        obstacles: Vec::new(),
+        bonds: Vec::new(),
+        obstacle_broadphase: obstacle::Broadphase::default(),
+        reaction_chunks: Vec::new(),
+        diagnostic_chunks: Vec::new(),
+        rate_chunks: Vec::new(),
+        soa_chunks: Vec::new(),
+        compute: None,
+        diagnostics: Default::default(),
+        diagnostics_history: Default::default(),
+        emitters: Vec::new(),
+        sinks: Vec::new(),
+        spawned: 0,
+        absorbed: 0,
+        expired: 0,
+        num_mortal: 0,
+        broken_bonds: 0,
+        max_rates: None,
+        accelerations: Vec::new(),
+        quadtree: quadtree::Quadtree::default(),
+        grid: grid::Grid::default(),
+        collision_corrections: Vec::new(),
+        densities: Vec::new(),
+        settings: Settings::default(),
+        max_particles: 2i32.pow(18),
+        benchmark_update: 0.0,
+        benchmark_draw: 0.0,
+        threadpool: threadpool::Threadpool::new_with_threads(2),
+        rng: fastrand::Rng::new(),
+        step_count: 0,
+    })
+}
+
 fn main() {
+    if std::env::args().any(|arg| arg == "--bench") {
+        benchmark::run(new_world());
+        return
+    }
+    if std::env::args().any(|arg| arg == "--verify-gpu") {
+        compute::verify(new_world());
+        return
+    }
+
     let event_loop = winit::event_loop::EventLoop::new();
 
     let window = winit::window::WindowBuilder::new()
@@ -31,21 +137,8 @@ fn main() {
     
     let mut renderer = renderer::Renderer::new(&window, &mut gui.imgui);
 
-    let mut world = Box::new(World {
-        position_camera: Point2D::new(SIZE_X * PRECISION / 2, SIZE_Y * PRECISION / 2),
-        size_field: (SIZE_X * PRECISION, SIZE_Y * PRECISION),
-        zoom: 0.5 * PRECISION as f32,
-        debug: false,
-        last_update: std::time::Instant::now(),
-        elapsed_since_update: 1.0,
-        entities: Vec::new(),
-        entities_gravity: Vec::new(),
-        settings: Settings::default(),
-        num_particles: 2i32.pow(18),
-        benchmark_update: 0.0,
-        benchmark_draw: 0.0,
-        threadpool: threadpool::Threadpool::new_with_threads(2),
-    });
+    let mut world = new_world();
+    world.compute = Some(renderer.create_compute());
     world.reset();
 
     let mut mouse_position = winit::dpi::PhysicalPosition::<f64>::default();
@@ -58,7 +151,7 @@ fn main() {
 
         match event {
             winit::event::Event::MainEventsCleared => {
-                if run == false { return }
+                if !run { return }
                 let draw_data = gui.update(&window, &mut world);
 
                 world.update();
@@ -72,12 +165,13 @@ fn main() {
                 use winit::event::ElementState;
                 match event {
                     WindowEvent::CloseRequested => {
+                        if let Some(mut compute) = world.compute.take() { compute.destroy(); }
                         renderer.destroy();
                         *control_flow = winit::event_loop::ControlFlow::Exit;
                         run = false;
                     }
                     WindowEvent::Resized(size) => {
-                        world.resize((size.width as i32, size.height as i32));
+                        world.resize((size.width as Coordinate, size.height as Coordinate));
                         renderer.resize(&mut gui.imgui, size);
                     }
                     WindowEvent::CursorMoved{device_id:_, position, ..} => {
@@ -93,36 +187,30 @@ fn main() {
                     }
                     WindowEvent::MouseInput{device_id: _, state, button, ..} => {
                         use winit::event::MouseButton;
-                        match button {
-                            MouseButton::Left => {
-                                match state {
-                                    ElementState::Pressed => {
-                                        if !gui.mouse_on_gui() {
-                                            world.settings.mouse_gravity_active = true;
-                                        }
-                                        world.update_mouse_gravity_info(mouse_position, window_size);
-                                    }
-                                    ElementState::Released => {
-                                        world.settings.mouse_gravity_active = false;
+                        if button == MouseButton::Left {
+                            match state {
+                                ElementState::Pressed => {
+                                    if !gui.mouse_on_gui() {
+                                        world.settings.mouse_gravity_active = true;
                                     }
+                                    world.update_mouse_gravity_info(mouse_position, window_size);
+                                }
+                                ElementState::Released => {
+                                    world.settings.mouse_gravity_active = false;
                                 }
                             }
-                            _ => {  }
                         }
                     }
                     WindowEvent::KeyboardInput{device_id: _, input, is_synthetic: _} => {
                         use winit::event::VirtualKeyCode;
-                        match input.state {
-                            ElementState::Pressed => {
-                                match input.virtual_keycode {
-                                    Some(VirtualKeyCode::D) => { world.change_debug(); }
-                                    Some(VirtualKeyCode::R) => { world.reset(); }
-                                    Some(VirtualKeyCode::S) => { world.stop_speed(); }
-                                    Some(VirtualKeyCode::T) => { world.tilt(); }
-                                    _ => {  }
-                                }
-                            },
-                            _ => {  }
+                        if input.state == ElementState::Pressed {
+                            match input.virtual_keycode {
+                                Some(VirtualKeyCode::D) => { world.change_debug(); }
+                                Some(VirtualKeyCode::R) => { world.reset(); }
+                                Some(VirtualKeyCode::S) => { world.stop_speed(); }
+                                Some(VirtualKeyCode::T) => { world.tilt(); }
+                                _ => {  }
+                            }
                         }
                     }
                     _ => {  }
@@ -0,0 +1,252 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::Entity;
+use crate::threadpool::Threadpool;
+use crate::world::Settings;
+
+use euclid::default::{Point2D, Vector2D};
+
+const CIRCLE_SEGMENTS: usize = 32;
+const CELL_SIZE: Coordinate = 32 * PRECISION;
+const MAX_CELLS_AXIS: Coordinate = 256;
+
+#[derive(Clone)]
+pub enum Obstacle {
+    Circle { center: Point2D<Coordinate>, radius: f32 },
+    Box { min: Point2D<Coordinate>, max: Point2D<Coordinate> },
+    Polygon { vertices: Vec<Point2D<Coordinate>> },
+}
+
+impl Obstacle {
+    pub const NAMES: [&'static str; 3] = ["circle", "box", "polygon"];
+
+    pub fn new(index: usize, center: Point2D<Coordinate>) -> Obstacle {
+        let half_size = 50 * PRECISION;
+        match index {
+            0 => { Obstacle::Circle { center, radius: 50.0 } }
+            1 => { Obstacle::Box { min: center - Vector2D::new(half_size, half_size), max: center + Vector2D::new(half_size, half_size) } }
+            _ => {
+                let vertices = (0..5).map(|i| {
+                    let angle = i as f32 / 5.0 * std::f32::consts::TAU;
+                    center + (Vector2D::new(angle.cos(), angle.sin()) * half_size as f32).round().cast()
+                }).collect();
+                Obstacle::Polygon { vertices }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Obstacle::Circle { .. } => { Obstacle::NAMES[0] }
+            Obstacle::Box { .. } => { Obstacle::NAMES[1] }
+            Obstacle::Polygon { .. } => { Obstacle::NAMES[2] }
+        }
+    }
+
+    pub fn bounds(&self) -> (Point2D<Coordinate>, Point2D<Coordinate>) {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let radius = (*radius * PRECISION as f32) as Coordinate;
+                (*center - Vector2D::new(radius, radius), *center + Vector2D::new(radius, radius))
+            }
+            Obstacle::Box { min, max } => { (min.min(*max), min.max(*max)) }
+            Obstacle::Polygon { vertices } => {
+                vertices.iter().fold((Point2D::new(Coordinate::MAX, Coordinate::MAX), Point2D::new(Coordinate::MIN, Coordinate::MIN)), |(min, max), v| (min.min(*v), max.max(*v)))
+            }
+        }
+    }
+
+    // Outward normal and penetration depth when the position is inside the obstacle
+    pub fn contact(&self, position: Point2D<Coordinate>) -> Option<(Vector2D<f32>, f32)> {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let relative = (position - *center).to_f32();
+                let radius = *radius * PRECISION as f32;
+                let distance = relative.length();
+                if distance >= radius { return None }
+                let normal = if distance > 0.0 { relative / distance } else { Vector2D::new(0.0, 1.0) };
+                Some((normal, radius - distance))
+            }
+            Obstacle::Box { min, max } => {
+                let (min, max) = (min.min(*max), min.max(*max));
+                if position.x <= min.x || position.y <= min.y || position.x >= max.x || position.y >= max.y { return None }
+                [
+                    (Vector2D::new(-1.0, 0.0), position.x - min.x),
+                    (Vector2D::new(1.0, 0.0), max.x - position.x),
+                    (Vector2D::new(0.0, -1.0), position.y - min.y),
+                    (Vector2D::new(0.0, 1.0), max.y - position.y),
+                ].into_iter().min_by_key(|(_, depth)| *depth).map(|(normal, depth)| (normal, depth as f32))
+            }
+            Obstacle::Polygon { vertices } => {
+                if vertices.len() < 3 { return None }
+                let winding = signed_area(vertices).signum();
+                let mut best: Option<(Vector2D<f32>, f32)> = None;
+                for i in 0..vertices.len() {
+                    let a = vertices[i];
+                    let b = vertices[(i + 1) % vertices.len()];
+                    let edge = (b - a).to_f32();
+                    if edge.square_length() == 0.0 { continue }
+                    let normal = Vector2D::new(edge.y, -edge.x).normalize() * winding;
+                    let distance = (position - a).to_f32().dot(normal);
+                    if distance >= 0.0 { return None }
+                    if best.is_none_or(|(_, depth)| -distance < depth) { best = Some((normal, -distance)); }
+                }
+                best
+            }
+        }
+    }
+
+    pub fn triangles(&self, output: &mut Vec<Point2D<Coordinate>>) {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let radius = *radius * PRECISION as f32;
+                let point = |i: usize| {
+                    let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    *center + (Vector2D::new(angle.cos(), angle.sin()) * radius).round().cast()
+                };
+                for i in 0..CIRCLE_SEGMENTS {
+                    output.extend_from_slice(&[*center, point(i), point(i + 1)]);
+                }
+            }
+            Obstacle::Box { min, max } => {
+                let (a, b, c, d) = (*min, Point2D::new(max.x, min.y), *max, Point2D::new(min.x, max.y));
+                output.extend_from_slice(&[a, b, c, a, c, d]);
+            }
+            Obstacle::Polygon { vertices } => {
+                for i in 1..vertices.len().saturating_sub(1) {
+                    output.extend_from_slice(&[vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+        }
+    }
+}
+
+fn signed_area(vertices: &[Point2D<Coordinate>]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..vertices.len() {
+        let a = vertices[i].to_f32();
+        let b = vertices[(i + 1) % vertices.len()].to_f32();
+        area += a.x * b.y - b.x * a.y;
+    }
+    area
+}
+
+#[derive(Default)]
+pub struct Broadphase {
+    cell_size: Coordinate,
+    size: (Coordinate, Coordinate),
+    cells: Vec<Vec<u32>>,
+}
+
+impl Broadphase {
+    pub fn build(&mut self, obstacles: &[Obstacle], size_field: (Coordinate, Coordinate)) {
+        self.cell_size = CELL_SIZE.max((size_field.0.max(size_field.1) + MAX_CELLS_AXIS - 1) / MAX_CELLS_AXIS);
+        self.size = ((size_field.0 + self.cell_size - 1) / self.cell_size, (size_field.1 + self.cell_size - 1) / self.cell_size);
+        self.size = (self.size.0.max(1), self.size.1.max(1));
+        self.cells.iter_mut().for_each(|cell| cell.clear());
+        self.cells.resize((self.size.0 * self.size.1) as usize, Vec::new());
+
+        for (index, obstacle) in obstacles.iter().enumerate() {
+            let (min, max) = obstacle.bounds();
+            let (min_x, min_y) = self.cell(min);
+            let (max_x, max_y) = self.cell(max);
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    self.cells[(y * self.size.0 + x) as usize].push(index as u32);
+                }
+            }
+        }
+    }
+
+    fn cell(&self, position: Point2D<Coordinate>) -> (Coordinate, Coordinate) {
+        (
+            (position.x / self.cell_size).clamp(0, self.size.0 - 1),
+            (position.y / self.cell_size).clamp(0, self.size.1 - 1),
+        )
+    }
+
+    pub fn candidates(&self, position: Point2D<Coordinate>) -> &[u32] {
+        if self.cells.is_empty() { return &[] }
+        let (x, y) = self.cell(position);
+        &self.cells[(y * self.size.0 + x) as usize]
+    }
+}
+
+pub fn resolve(entities: &mut [Entity], obstacles: &[Obstacle], broadphase: &Broadphase, settings: &Settings, threadpool: &mut Threadpool) {
+    threadpool.for_each_chunk_mut(entities, |_chunk, _offset, entities| {
+        for entity in entities.iter_mut() {
+            for index in broadphase.candidates(entity.position) {
+                if let Some((normal, depth)) = obstacles[*index as usize].contact(entity.position) {
+                    entity.position += (normal * (depth + 1.0)).round().cast();
+
+                    let speed_normal = entity.speed.dot(normal);
+                    if speed_normal < 0.0 {
+                        let speed_tangent = entity.speed - normal * speed_normal;
+                        entity.speed = speed_tangent * (1.0 - settings.obstacle_friction) - normal * speed_normal * settings.obstacle_restitution;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obstacles(center: Point2D<Coordinate>) -> Vec<Obstacle> {
+        let mut obstacles: Vec<Obstacle> = (0..Obstacle::NAMES.len()).map(|index| Obstacle::new(index, center)).collect();
+        // The same pentagon wound the other way
+        if let Obstacle::Polygon { vertices } = obstacles[2].clone() { obstacles.push(Obstacle::Polygon { vertices: vertices.into_iter().rev().collect() }); }
+        obstacles
+    }
+
+    #[test]
+    fn contact_points_outward() {
+        let center = Point2D::new(500 * PRECISION, 500 * PRECISION);
+        for obstacle in obstacles(center) {
+            let inside = center + Vector2D::new(30 * PRECISION, 5 * PRECISION);
+            let (normal, depth) = obstacle.contact(inside).unwrap_or_else(|| panic!("{}", obstacle.name()));
+            assert!(normal.dot((inside - center).to_f32()) > 0.0 && depth > 0.0, "{} {:?} {}", obstacle.name(), normal, depth);
+            assert!(obstacle.contact(center + Vector2D::new(60 * PRECISION, 0)).is_none(), "{}", obstacle.name());
+        }
+    }
+
+    #[test]
+    fn resolve_reflects_with_restitution_and_friction() {
+        let mut threadpool = Threadpool::new_with_threads(2);
+        let settings = Settings { obstacle_restitution: 0.5, obstacle_friction: 0.25, ..Settings::default() };
+        let size_field = (1_000 * PRECISION, 1_000 * PRECISION);
+        let center = Point2D::new(500 * PRECISION, 500 * PRECISION);
+        for obstacle in obstacles(center) {
+            let obstacles = [obstacle];
+            let mut broadphase = Broadphase::default();
+            broadphase.build(&obstacles, size_field);
+            // Just inside the right side, moving left into the obstacle and down along it
+            let mut entities = vec![Entity { position: center + Vector2D::new(30 * PRECISION, 5 * PRECISION), speed: Vector2D::new(-1_000.0, 400.0), ..Entity::default() }];
+            resolve(&mut entities, &obstacles, &broadphase, &settings, &mut threadpool);
+
+            let name = obstacles[0].name();
+            assert!(obstacles[0].contact(entities[0].position).is_none(), "{}", name);
+            let (normal, _) = obstacles[0].contact(center + Vector2D::new(30 * PRECISION, 5 * PRECISION)).unwrap();
+            let speed_normal = Vector2D::new(-1_000.0, 400.0).dot(normal);
+            let speed_tangent = Vector2D::new(-1_000.0, 400.0) - normal * speed_normal;
+            let expected = speed_tangent * 0.75 - normal * speed_normal * 0.5;
+            assert!((entities[0].speed - expected).length() < 1e-2, "{} {:?} {:?}", name, entities[0].speed, expected);
+        }
+    }
+
+    #[test]
+    fn broadphase_candidates_contain_every_touched_obstacle() {
+        let rng = fastrand::Rng::with_seed(5);
+        let size_field = (20_000 * PRECISION, 9_000 * PRECISION);
+        let obstacles: Vec<Obstacle> = (0..60).map(|i| Obstacle::new(i % 3, Entity::new_random(size_field, &rng).position)).collect();
+        let mut broadphase = Broadphase::default();
+        broadphase.build(&obstacles, size_field);
+        for _ in 0..20_000 {
+            let position = Entity::new_random(size_field, &rng).position;
+            for (index, obstacle) in obstacles.iter().enumerate() {
+                if obstacle.contact(position).is_some() { assert!(broadphase.candidates(position).contains(&(index as u32))); }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::PRECISION;
+use crate::entity::Entity;
+use crate::grid::Grid;
+use crate::threadpool::Threadpool;
+use crate::world::Settings;
+
+use euclid::default::Vector2D;
+
+const BETA: f32 = 0.3;
+
+// Repulsive below BETA * radius whatever the species, then a bump scaled by the attraction of the matrix
+fn force(distance_relative: f32, attraction: f32) -> f32 {
+    if distance_relative < BETA {
+        distance_relative / BETA - 1.0
+    } else if distance_relative < 1.0 {
+        attraction * (1.0 - (2.0 * distance_relative - 1.0 - BETA).abs() / (1.0 - BETA))
+    } else {
+        0.0
+    }
+}
+
+pub fn update_accelerations(entities: &[Entity], grid: &Grid, settings: &Settings, accelerations: &mut Vec<Vector2D<f32>>, threadpool: &mut Threadpool) {
+    accelerations.resize(entities.len(), Vector2D::zero());
+    let radius = settings.life_radius * PRECISION as f32;
+    let strength = settings.life_strength * PRECISION as f32;
+
+    threadpool.for_each_chunk_mut(accelerations, |_chunk, offset, accelerations| {
+        for (i, acceleration) in accelerations.iter_mut().enumerate() {
+            let index = offset + i;
+            let entity = &entities[index];
+            let attractions = &settings.species_matrix[entity.species as usize];
+
+            let mut total = Vector2D::zero();
+            grid.for_each_neighbour(entities, entity.position, radius, |other, direction, distance_squared| {
+                if other == index || distance_squared == 0.0 { return }
+                let distance = distance_squared.sqrt();
+                total += direction / distance * force(distance / radius, attractions[entities[other].species as usize]);
+            });
+
+            *acceleration = total * strength;
+        }
+    });
+}
\ No newline at end of file
@@ -0,0 +1,208 @@
+use crate::Coordinate;
+use crate::entity::Entity;
+
+use euclid::default::{Point2D, Vector2D};
+
+const LEAF_CAPACITY: u32 = 8;
+const MAX_DEPTH: usize = 24;
+
+#[derive(Clone, Default)]
+struct Node {
+    center: Point2D<f32>,
+    half_size: f32,
+    center_of_mass: Point2D<f32>,
+    mass: f32,
+    first_child: u32,
+    begin: u32,
+    end: u32,
+}
+
+#[derive(Default)]
+pub struct Quadtree {
+    nodes: Vec<Node>,
+    indices: Vec<u32>,
+    positions: Vec<Point2D<f32>>,
+    masses: Vec<f32>,
+    extent: Vector2D<f32>,
+    wrap: (bool, bool),
+}
+
+impl Quadtree {
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn build(&mut self, entities: &[Entity], size_field: (Coordinate, Coordinate), wrap: (bool, bool)) {
+        self.nodes.clear();
+        self.indices.clear();
+        self.positions.clear();
+        self.masses.clear();
+        self.extent = Vector2D::new(size_field.0 as f32, size_field.1 as f32);
+        self.wrap = wrap;
+        if entities.is_empty() { return }
+
+        self.indices.extend(0..entities.len() as u32);
+        self.positions.extend(entities.iter().map(|entity| entity.position.to_f32()));
+        self.masses.extend(entities.iter().map(|entity| entity.mass));
+
+        let (mut min, mut max) = (self.positions[0], self.positions[0]);
+        for position in self.positions.iter() {
+            min = min.min(*position);
+            max = max.max(*position);
+        }
+
+        self.nodes.push(Node {
+            center: min.lerp(max, 0.5),
+            half_size: (max.x - min.x).max(max.y - min.y) / 2.0 + 1.0,
+            begin: 0,
+            end: entities.len() as u32,
+            ..Default::default()
+        });
+        self.subdivide(0, 0);
+    }
+
+    fn subdivide(&mut self, node_index: usize, depth: usize) {
+        let Node { center, half_size, begin, end, .. } = self.nodes[node_index];
+
+        let (mut sum, mut mass) = (Vector2D::zero(), 0.0);
+        for index in self.indices[begin as usize..end as usize].iter() {
+            sum += self.positions[*index as usize].to_vector() * self.masses[*index as usize];
+            mass += self.masses[*index as usize];
+        }
+        self.nodes[node_index].mass = mass;
+        self.nodes[node_index].center_of_mass = if mass > 0.0 { (sum / mass).to_point() } else { center };
+
+        if end - begin <= LEAF_CAPACITY || depth >= MAX_DEPTH { return }
+
+        let positions = &self.positions;
+        let indices = &mut self.indices[begin as usize..end as usize];
+        let split_y = partition(indices, |index| positions[index as usize].y < center.y);
+        let split_x_low = partition(&mut indices[..split_y], |index| positions[index as usize].x < center.x);
+        let split_x_high = split_y + partition(&mut indices[split_y..], |index| positions[index as usize].x < center.x);
+
+        let bounds = [begin, begin + split_x_low as u32, begin + split_y as u32, begin + split_x_high as u32, end];
+        let quarter = half_size / 2.0;
+        let first_child = self.nodes.len();
+        self.nodes[node_index].first_child = first_child as u32;
+        for quadrant in 0..4 {
+            let offset = Vector2D::new(
+                if quadrant & 1 == 0 { -quarter } else { quarter },
+                if quadrant & 2 == 0 { -quarter } else { quarter }
+            );
+            self.nodes.push(Node {
+                center: center + offset,
+                half_size: quarter,
+                begin: bounds[quadrant],
+                end: bounds[quadrant + 1],
+                ..Default::default()
+            });
+        }
+        for quadrant in 0..4 {
+            self.subdivide(first_child + quadrant, depth + 1);
+        }
+    }
+
+    // Shortest offset on wrapped axes, like Entity::direction_to
+    fn minimum_image(&self, mut direction: Vector2D<f32>) -> Vector2D<f32> {
+        if self.wrap.0 { direction.x -= self.extent.x * (direction.x / self.extent.x).round(); }
+        if self.wrap.1 { direction.y -= self.extent.y * (direction.y / self.extent.y).round(); }
+        direction
+    }
+
+    // Sum of mass * direction / (distance² + softening²)^(3/2) over every other entity, distances are minimum images on wrapped axes
+    pub fn acceleration(&self, index: usize, theta: f32, softening: f32) -> Vector2D<f32> {
+        let mut acceleration = Vector2D::zero();
+        if self.nodes.is_empty() { return acceleration }
+
+        let position = self.positions[index];
+        let theta_squared = theta * theta;
+        let softening_squared = softening * softening;
+
+        let mut stack = [0u32; 4 * MAX_DEPTH + 4];
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len] as usize];
+            if node.mass == 0.0 { continue }
+
+            if node.first_child == 0 {
+                for other in self.indices[node.begin as usize..node.end as usize].iter() {
+                    if *other as usize == index { continue }
+                    let direction = self.minimum_image(self.positions[*other as usize] - position);
+                    let distance_squared = direction.square_length() + softening_squared;
+                    acceleration += direction * (self.masses[*other as usize] / (distance_squared * distance_squared.sqrt()));
+                }
+                continue
+            }
+
+            let direction = self.minimum_image(node.center_of_mass - position);
+            let distance_squared = direction.square_length();
+            let size = node.half_size * 2.0;
+            let offset = self.minimum_image(position - node.center);
+            let inside = offset.x.abs() <= node.half_size && offset.y.abs() <= node.half_size;
+            // A node crossing the line opposite the position has members imaged to both sides, its centre of mass stands for neither
+            let straddles = (self.wrap.0 && offset.x.abs() + node.half_size > self.extent.x / 2.0) || (self.wrap.1 && offset.y.abs() + node.half_size > self.extent.y / 2.0);
+            if !inside && !straddles && size * size < theta_squared * distance_squared {
+                let distance_squared = distance_squared + softening_squared;
+                acceleration += direction * (node.mass / (distance_squared * distance_squared.sqrt()));
+            } else {
+                for child in 0..4 {
+                    stack[stack_len] = node.first_child + child;
+                    stack_len += 1;
+                }
+            }
+        }
+
+        acceleration
+    }
+}
+
+fn partition<F: Fn(u32) -> bool>(indices: &mut [u32], predicate: F) -> usize {
+    let mut split = 0;
+    for i in 0..indices.len() {
+        if predicate(indices[i]) {
+            indices.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exact sum, with the sum of the term magnitudes as the scale of the error
+    fn brute_force(quadtree: &Quadtree, index: usize, softening: f32) -> (Vector2D<f32>, f32) {
+        let (mut acceleration, mut scale) = (Vector2D::zero(), 0.0);
+        for other in 0..quadtree.positions.len() {
+            if other == index { continue }
+            let direction = quadtree.minimum_image(quadtree.positions[other] - quadtree.positions[index]);
+            let distance_squared = direction.square_length() + softening * softening;
+            let term = direction * (quadtree.masses[other] / (distance_squared * distance_squared.sqrt()));
+            acceleration += term;
+            scale += term.length();
+        }
+        (acceleration, scale)
+    }
+
+    #[test]
+    fn acceleration_matches_brute_force_with_masses_and_wrap() {
+        let rng = fastrand::Rng::with_seed(3);
+        let size_field = (100_000, 70_000);
+        let entities: Vec<Entity> = (0..1_000).map(|_| Entity { mass: rng.f32() * 4.0, ..Entity::new_random(size_field, &rng) }).collect();
+
+        let mut quadtree = Quadtree::default();
+        for wrap in [(false, false), (true, false), (true, true)] {
+            quadtree.build(&entities, size_field, wrap);
+            for index in 0..entities.len() {
+                let (expected, scale) = brute_force(&quadtree, index, 100.0);
+                // Opening every node is exact up to rounding, theta 0.5 stays within a percent of the summed magnitudes
+                let exact = quadtree.acceleration(index, 0.0, 100.0);
+                assert!((exact - expected).length() <= scale * 1e-4, "wrap {:?} index {}", wrap, index);
+                let approximate = quadtree.acceleration(index, 0.5, 100.0);
+                assert!((approximate - expected).length() <= scale * 0.01, "wrap {:?} index {}", wrap, index);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -2,17 +2,55 @@
 //use crate::world::*;
 
 #[allow(unused)]
-#[repr(packed(1))]
+#[repr(C, packed(1))]
 pub struct Uniform {
     window_size: (i32, i32),
-    camera: (i32, i32),
     zoom: f32,
     alpha: f32,
-    void: [u8; 8],
     color_base: (f32, f32, f32),
     void_2: [u8; 4],
     color_fast: (f32, f32, f32),
     color_ratio: f32,
+    color_mode: u32,
+    void_3: [u8; 12],
+    species_colors: [[f32; 4]; crate::world::MAX_SPECIES],
+}
+
+// Vertices are uploaded relative to the camera so the GPU never sees world coordinates
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ParticleVertex {
+    position: euclid::default::Point2D<i32>,
+    speed: euclid::default::Vector2D<f32>,
+    species: u32,
+    charge: f32,
+}
+
+impl ParticleVertex {
+    pub fn new(entity: &crate::entity::Entity, position: euclid::default::Point2D<crate::Coordinate>, camera: euclid::default::Point2D<crate::Coordinate>) -> ParticleVertex {
+        ParticleVertex {
+            position: relative_position(position, camera),
+            speed: entity.speed,
+            species: entity.species,
+            charge: entity.charge,
+        }
+    }
+}
+
+// Clamped far off screen instead of overflowing, the casts are no-ops when large_world makes Coordinate i64
+#[allow(clippy::unnecessary_cast)]
+pub fn relative_position(position: euclid::default::Point2D<crate::Coordinate>, camera: euclid::default::Point2D<crate::Coordinate>) -> euclid::default::Point2D<i32> {
+    let relative = |value: crate::Coordinate, origin: crate::Coordinate| {
+        (value as i64 - origin as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    };
+    euclid::default::Point2D::new(relative(position.x, camera.x), relative(position.y, camera.y))
+}
+
+#[repr(C)]
+pub struct GravityVertex {
+    vertex: ParticleVertex,
+    force: f32,
+    law: u32,
 }
 
 
@@ -75,7 +113,7 @@ impl ImguiRenderer {
         ).unwrap();
 
         ImguiRenderer {
-            render_pass: render_pass,
+            render_pass,
             renderer: Some(renderer),
         }
     }
@@ -115,7 +153,7 @@ impl GravityPipeline {
         
             let vertex_input_binding = ash::vk::VertexInputBindingDescription {
                 binding: 0,
-                stride: std::mem::size_of::<((f32, f32), (f32, f32), f32)>() as u32,
+                stride: std::mem::size_of::<GravityVertex>() as u32,
                 input_rate: ash::vk::VertexInputRate::VERTEX,
             };
             let vertex_input_attribute = ash::vk::VertexInputAttributeDescription {
@@ -134,10 +172,16 @@ impl GravityPipeline {
                 binding: 0,
                 location: 2,
                 format: ash::vk::Format::R32_SFLOAT,
-                offset: 16,
+                offset: std::mem::size_of::<ParticleVertex>() as u32,
+            };
+            let vertex_input_attribute_4 = ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: ash::vk::Format::R32_UINT,
+                offset: std::mem::size_of::<ParticleVertex>() as u32 + 4,
             };
             let vertex_input_bindings = [vertex_input_binding];
-            let vertex_input_attributes = [vertex_input_attribute, vertex_input_attribute_2, vertex_input_attribute_3];
+            let vertex_input_attributes = [vertex_input_attribute, vertex_input_attribute_2, vertex_input_attribute_3, vertex_input_attribute_4];
             let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo::builder()
                 .vertex_binding_descriptions(&vertex_input_bindings)
                 .vertex_attribute_descriptions(&vertex_input_attributes);
@@ -247,6 +291,305 @@ impl GravityPipeline {
     }
 }
 
+pub struct ObstaclePipeline {
+    vertex_shader: ash::vk::ShaderModule,
+    fragment_shader: ash::vk::ShaderModule,
+    render_pass: ash::vk::RenderPass,
+    descriptor_set_layout: ash::vk::DescriptorSetLayout,
+    pipeline_layout: ash::vk::PipelineLayout,
+    pipeline: ash::vk::Pipeline,
+}
+
+impl ObstaclePipeline {
+    fn create(device: &ash_ez::Device, swapchain: &ash_ez::Swapchain, window_size: winit::dpi::PhysicalSize<u32>) -> ObstaclePipeline {
+        unsafe {
+            let vertex_shader_raw_u8 = include_bytes!("../shaders/compiled/vertex_obstacle.spv");
+            let fragment_shader_raw_u8 = include_bytes!("../shaders/compiled/fragment_obstacle.spv");
+        
+            let vertex_shader_raw = std::slice::from_raw_parts(vertex_shader_raw_u8.as_ptr() as *const u32, vertex_shader_raw_u8.len() / 4);
+            let fragment_shader_raw = std::slice::from_raw_parts(fragment_shader_raw_u8.as_ptr() as *const u32, fragment_shader_raw_u8.len() / 4);
+        
+            let vertex_shader = device.create_shader(vertex_shader_raw);
+            let fragment_shader = device.create_shader(fragment_shader_raw);
+        
+            let main_str = std::ffi::CString::new("main").unwrap();
+        
+            let stage_vertex = ash_ez::utils::pipeline_shader_stage_create_info_helper(vertex_shader, ash::vk::ShaderStageFlags::VERTEX, &main_str);
+            let stage_fragment = ash_ez::utils::pipeline_shader_stage_create_info_helper(fragment_shader, ash::vk::ShaderStageFlags::FRAGMENT, &main_str);
+            let stages = [stage_vertex, stage_fragment];
+        
+            let vertex_input_binding = ash::vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: std::mem::size_of::<euclid::default::Point2D<i32>>() as u32,
+                input_rate: ash::vk::VertexInputRate::VERTEX,
+            };
+            let vertex_input_attribute = ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: ash::vk::Format::R32G32_SINT,
+                offset: 0,
+            };
+            let vertex_input_bindings = [vertex_input_binding];
+            let vertex_input_attributes = [vertex_input_attribute];
+            let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&vertex_input_bindings)
+                .vertex_attribute_descriptions(&vertex_input_attributes);
+            
+            let input_assembly_state = ash::vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(ash::vk::PrimitiveTopology::TRIANGLE_LIST);
+            
+            let viewport = [ash_ez::utils::viewport_helper(window_size.width as f32, window_size.height as f32)];
+            let scissor = [ash_ez::utils::scissor_helper(window_size.width, window_size.height)];
+            let viewport_state = ash_ez::utils::pipeline_viewport_state_create_info_helper_2(&viewport, &scissor);
+            let rasterization_state = ash_ez::utils::pipeline_rasterization_state_create_info_helper();
+            let multisample_state = ash_ez::utils::pipeline_multisample_state_create_info_helper();
+            let color_blend_attachment_state = ash_ez::utils::pipeline_color_blend_attachment_state_helper();
+            let color_blend_attachment_states = [color_blend_attachment_state];
+            let color_blend_state = ash::vk::PipelineColorBlendStateCreateInfo::builder()
+                .attachments(&color_blend_attachment_states);
+
+            let descriptor_set_layout_binding = ash::vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(ash::vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT)
+                .build();
+            let bindings = [descriptor_set_layout_binding];
+
+            let descriptor_set_layout_create_info = ash::vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+            let descriptor_set_layout = device.raw.create_descriptor_set_layout(&descriptor_set_layout_create_info, None).unwrap();
+            let descriptor_set_layouts = [descriptor_set_layout];
+
+            let pipeline_layout_info = ash::vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&descriptor_set_layouts);
+                
+            let pipeline_layout = device.raw.create_pipeline_layout(&pipeline_layout_info, None).unwrap();
+            
+            let attachment_description = ash::vk::AttachmentDescription::builder()
+                .format(swapchain.format)
+                .samples(ash::vk::SampleCountFlags::TYPE_1)
+                .load_op(ash::vk::AttachmentLoadOp::LOAD)
+                .store_op(ash::vk::AttachmentStoreOp::STORE)
+                .initial_layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build();
+            
+            let attachment_descriptions = [attachment_description];
+        
+            let attachment_reference = ash::vk::AttachmentReference::builder()
+                .attachment(0)
+                .layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build();
+            
+            let attachment_references = [attachment_reference];
+            
+            let subpass_description = ash::vk::SubpassDescription::builder()
+                .pipeline_bind_point(ash::vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&attachment_references)
+                .build();
+                
+            let subpass_descriptions = [subpass_description];
+            
+            let render_pass_info = ash::vk::RenderPassCreateInfo::builder()
+                .attachments(&attachment_descriptions)
+                .subpasses(&subpass_descriptions);
+            
+            let render_pass = device.raw.create_render_pass(&render_pass_info, None).unwrap();
+        
+            let pipeline_create_info = ash::vk::GraphicsPipelineCreateInfo::builder()
+                .stages(&stages)
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state)
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterization_state)
+                .multisample_state(&multisample_state)
+                //.depth_stencil_state(depth_stencil_state)
+                .color_blend_state(&color_blend_state)
+                .layout(pipeline_layout)
+                .render_pass(render_pass)
+                .subpass(0)
+                .build();
+        
+            let pipeline_create_infos = [pipeline_create_info];
+            let pipeline_cache = ash::vk::PipelineCache::null();
+            let pipelines = device.raw.create_graphics_pipelines(pipeline_cache, &pipeline_create_infos, None).unwrap();
+            let pipeline = pipelines[0];
+
+            ObstaclePipeline {
+                vertex_shader,
+                fragment_shader,
+                render_pass,
+                descriptor_set_layout,
+                pipeline_layout,
+                pipeline,
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash_ez::Device) {
+        unsafe {
+            device.raw.destroy_pipeline(self.pipeline, None);
+            device.raw.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.raw.destroy_render_pass(self.render_pass, None);
+            device.raw.destroy_shader_module(self.vertex_shader, None);
+            device.raw.destroy_shader_module(self.fragment_shader, None);
+            device.raw.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+pub struct BondPipeline {
+    vertex_shader: ash::vk::ShaderModule,
+    fragment_shader: ash::vk::ShaderModule,
+    render_pass: ash::vk::RenderPass,
+    descriptor_set_layout: ash::vk::DescriptorSetLayout,
+    pipeline_layout: ash::vk::PipelineLayout,
+    pipeline: ash::vk::Pipeline,
+}
+
+impl BondPipeline {
+    fn create(device: &ash_ez::Device, swapchain: &ash_ez::Swapchain, window_size: winit::dpi::PhysicalSize<u32>) -> BondPipeline {
+        unsafe {
+            // Same camera transform as the obstacles
+            let vertex_shader_raw_u8 = include_bytes!("../shaders/compiled/vertex_obstacle.spv");
+            let fragment_shader_raw_u8 = include_bytes!("../shaders/compiled/fragment_bond.spv");
+        
+            let vertex_shader_raw = std::slice::from_raw_parts(vertex_shader_raw_u8.as_ptr() as *const u32, vertex_shader_raw_u8.len() / 4);
+            let fragment_shader_raw = std::slice::from_raw_parts(fragment_shader_raw_u8.as_ptr() as *const u32, fragment_shader_raw_u8.len() / 4);
+        
+            let vertex_shader = device.create_shader(vertex_shader_raw);
+            let fragment_shader = device.create_shader(fragment_shader_raw);
+        
+            let main_str = std::ffi::CString::new("main").unwrap();
+        
+            let stage_vertex = ash_ez::utils::pipeline_shader_stage_create_info_helper(vertex_shader, ash::vk::ShaderStageFlags::VERTEX, &main_str);
+            let stage_fragment = ash_ez::utils::pipeline_shader_stage_create_info_helper(fragment_shader, ash::vk::ShaderStageFlags::FRAGMENT, &main_str);
+            let stages = [stage_vertex, stage_fragment];
+        
+            let vertex_input_binding = ash::vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: std::mem::size_of::<euclid::default::Point2D<i32>>() as u32,
+                input_rate: ash::vk::VertexInputRate::VERTEX,
+            };
+            let vertex_input_attribute = ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: ash::vk::Format::R32G32_SINT,
+                offset: 0,
+            };
+            let vertex_input_bindings = [vertex_input_binding];
+            let vertex_input_attributes = [vertex_input_attribute];
+            let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&vertex_input_bindings)
+                .vertex_attribute_descriptions(&vertex_input_attributes);
+            
+            let input_assembly_state = ash::vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(ash::vk::PrimitiveTopology::LINE_LIST);
+            
+            let viewport = [ash_ez::utils::viewport_helper(window_size.width as f32, window_size.height as f32)];
+            let scissor = [ash_ez::utils::scissor_helper(window_size.width, window_size.height)];
+            let viewport_state = ash_ez::utils::pipeline_viewport_state_create_info_helper_2(&viewport, &scissor);
+            let rasterization_state = ash_ez::utils::pipeline_rasterization_state_create_info_helper();
+            let multisample_state = ash_ez::utils::pipeline_multisample_state_create_info_helper();
+            let color_blend_attachment_state = ash_ez::utils::pipeline_color_blend_attachment_state_helper();
+            let color_blend_attachment_states = [color_blend_attachment_state];
+            let color_blend_state = ash::vk::PipelineColorBlendStateCreateInfo::builder()
+                .attachments(&color_blend_attachment_states);
+
+            let descriptor_set_layout_binding = ash::vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(ash::vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT)
+                .build();
+            let bindings = [descriptor_set_layout_binding];
+
+            let descriptor_set_layout_create_info = ash::vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+            let descriptor_set_layout = device.raw.create_descriptor_set_layout(&descriptor_set_layout_create_info, None).unwrap();
+            let descriptor_set_layouts = [descriptor_set_layout];
+
+            let pipeline_layout_info = ash::vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&descriptor_set_layouts);
+                
+            let pipeline_layout = device.raw.create_pipeline_layout(&pipeline_layout_info, None).unwrap();
+            
+            let attachment_description = ash::vk::AttachmentDescription::builder()
+                .format(swapchain.format)
+                .samples(ash::vk::SampleCountFlags::TYPE_1)
+                .load_op(ash::vk::AttachmentLoadOp::LOAD)
+                .store_op(ash::vk::AttachmentStoreOp::STORE)
+                .initial_layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build();
+            
+            let attachment_descriptions = [attachment_description];
+        
+            let attachment_reference = ash::vk::AttachmentReference::builder()
+                .attachment(0)
+                .layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build();
+            
+            let attachment_references = [attachment_reference];
+            
+            let subpass_description = ash::vk::SubpassDescription::builder()
+                .pipeline_bind_point(ash::vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&attachment_references)
+                .build();
+                
+            let subpass_descriptions = [subpass_description];
+            
+            let render_pass_info = ash::vk::RenderPassCreateInfo::builder()
+                .attachments(&attachment_descriptions)
+                .subpasses(&subpass_descriptions);
+            
+            let render_pass = device.raw.create_render_pass(&render_pass_info, None).unwrap();
+        
+            let pipeline_create_info = ash::vk::GraphicsPipelineCreateInfo::builder()
+                .stages(&stages)
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state)
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterization_state)
+                .multisample_state(&multisample_state)
+                //.depth_stencil_state(depth_stencil_state)
+                .color_blend_state(&color_blend_state)
+                .layout(pipeline_layout)
+                .render_pass(render_pass)
+                .subpass(0)
+                .build();
+        
+            let pipeline_create_infos = [pipeline_create_info];
+            let pipeline_cache = ash::vk::PipelineCache::null();
+            let pipelines = device.raw.create_graphics_pipelines(pipeline_cache, &pipeline_create_infos, None).unwrap();
+            let pipeline = pipelines[0];
+
+            BondPipeline {
+                vertex_shader,
+                fragment_shader,
+                render_pass,
+                descriptor_set_layout,
+                pipeline_layout,
+                pipeline,
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash_ez::Device) {
+        unsafe {
+            device.raw.destroy_pipeline(self.pipeline, None);
+            device.raw.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.raw.destroy_render_pass(self.render_pass, None);
+            device.raw.destroy_shader_module(self.vertex_shader, None);
+            device.raw.destroy_shader_module(self.fragment_shader, None);
+            device.raw.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
 pub struct ParticlesPipeline {
     vertex_shader: ash::vk::ShaderModule,
     fragment_shader: ash::vk::ShaderModule,
@@ -276,7 +619,7 @@ impl ParticlesPipeline {
         
             let vertex_input_binding = ash::vk::VertexInputBindingDescription {
                 binding: 0,
-                stride: std::mem::size_of::<((f32, f32), (f32, f32))>() as u32,
+                stride: std::mem::size_of::<ParticleVertex>() as u32,
                 input_rate: ash::vk::VertexInputRate::VERTEX,
             };
             let vertex_input_attribute = ash::vk::VertexInputAttributeDescription {
@@ -291,8 +634,20 @@ impl ParticlesPipeline {
                 format: ash::vk::Format::R32G32_SFLOAT,
                 offset: 8,
             };
+            let vertex_input_attribute_3 = ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: ash::vk::Format::R32_UINT,
+                offset: 16,
+            };
+            let vertex_input_attribute_4 = ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: ash::vk::Format::R32_SFLOAT,
+                offset: 20,
+            };
             let vertex_input_bindings = [vertex_input_binding];
-            let vertex_input_attributes = [vertex_input_attribute, vertex_input_attribute_2];
+            let vertex_input_attributes = [vertex_input_attribute, vertex_input_attribute_2, vertex_input_attribute_3, vertex_input_attribute_4];
             let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo::builder()
                 .vertex_binding_descriptions(&vertex_input_bindings)
                 .vertex_attribute_descriptions(&vertex_input_attributes);
@@ -423,6 +778,8 @@ pub struct Renderer {
 
     particles_pipeline: ParticlesPipeline,
     gravity_pipeline: GravityPipeline,
+    obstacle_pipeline: ObstaclePipeline,
+    bond_pipeline: BondPipeline,
 
     actual_image_index: u32,
 
@@ -442,11 +799,10 @@ impl Renderer {
             let physical_device = physical_devices.iter()
                 .filter(|physical_device| 
                     physical_device.has_graphics_queue() && 
-                    physical_device.is_discrete() &&
                     ash::vk::api_version_major(physical_device.properties.api_version) >= 1 &&
                     ash::vk::api_version_minor(physical_device.properties.api_version) >= 2
                 )
-                .find(|_| true).unwrap().clone();
+                .min_by_key(|physical_device| physical_device.preference()).unwrap().clone();
         
             let surface = instance.create_surface(window);
             let device = instance.create_device(&physical_device, &surface, ash::vk::PhysicalDeviceFeatures::builder().build(), vec!["VK_KHR_swapchain"] , vec!["VK_LAYER_KHRONOS_validation"]);
@@ -520,6 +876,8 @@ impl Renderer {
 
             let particles_pipeline = ParticlesPipeline::create(&device, &swapchain, window_size);
             let gravity_pipeline = GravityPipeline::create(&device, &swapchain, window_size);
+            let obstacle_pipeline = ObstaclePipeline::create(&device, &swapchain, window_size);
+            let bond_pipeline = BondPipeline::create(&device, &swapchain, window_size);
 
             let imgui_renderer = ImguiRenderer::new(imgui, &physical_device, &instance, &device, &swapchain, command_pool);
 
@@ -534,18 +892,20 @@ impl Renderer {
                 command_pool,
 
                 vertex_buffer_size: 1_000_000,
-                vertex_buffer: vertex_buffer,
+                vertex_buffer,
                 vertex_buffer_memory: vertex_allocated_memory,
 
                 staging_buffer_size: 1_000_000,
-                staging_buffer: staging_buffer,
+                staging_buffer,
                 staging_buffer_memory: staging_allocated_memory,
 
-                uniform_buffer: uniform_buffer,
+                uniform_buffer,
                 uniform_buffer_memory: uniform_allocated_memory,
 
                 particles_pipeline,
                 gravity_pipeline,
+                obstacle_pipeline,
+                bond_pipeline,
 
                 actual_image_index: 0,
 
@@ -554,6 +914,10 @@ impl Renderer {
         }
     }
 
+    pub fn create_compute(&self) -> crate::compute::Compute {
+        crate::compute::Compute::new(&self.device, &self.physical_device)
+    }
+
     fn update_vertex_buffer_size(&mut self, new_size: u64) {
         if self.vertex_buffer_size >= new_size { return }
 
@@ -617,7 +981,7 @@ impl Renderer {
     }
 
     fn update_vertex_buffer(&mut self, data: &[u8]) {
-        if data.len() == 0 { return }
+        if data.is_empty() { return }
         self.update_vertex_buffer_size(data.len() as u64);
         self.update_staging_buffer_size(data.len() as u64);
         unsafe {
@@ -676,24 +1040,22 @@ impl Renderer {
             let fences = [fence];
             let result = self.swapchain.util.acquire_next_image(self.swapchain.raw, u64::MAX, ash::vk::Semaphore::null(), fence);
             if result.is_err() { return }
-            let (image_index, suboptimal) = result.unwrap();
-            if suboptimal == true {
-                
-            }
+            let (image_index, _suboptimal) = result.unwrap();
             self.actual_image_index = image_index;
             self.device.raw.wait_for_fences(&fences, true, u64::MAX).unwrap();
             self.device.raw.destroy_fence(fence, None);
 
             let uniform = Uniform {
                 window_size: (self.swapchain.extent.width as i32, self.swapchain.extent.height as i32),
-                camera: world.position_camera.into(),
                 zoom: world.zoom,
                 alpha: world.settings.alpha,
-                void: Default::default(),
                 void_2: Default::default(),
                 color_base: world.settings.color_base,
                 color_fast: world.settings.color_fast,
                 color_ratio: world.settings.color_ratio_speed,
+                color_mode: world.settings.color_mode as u32,
+                void_3: Default::default(),
+                species_colors: world.settings.species_colors.map(|(r, g, b)| [r, g, b, 1.0]),
             };
 
             self.update_uniform(uniform);
@@ -739,6 +1101,8 @@ impl Renderer {
             let framebuffer = self.device.raw.create_framebuffer(&framebuffer_create_info, None).unwrap();
 
             self.draw_particles(framebuffer, descriptor_set, world);
+            self.draw_obstacles(framebuffer, descriptor_set, world);
+            self.draw_bonds(framebuffer, descriptor_set, world);
             self.draw_gravity(framebuffer, descriptor_set, world);
             
             self.device.raw.destroy_descriptor_pool(descriptor_pool, None);
@@ -747,12 +1111,21 @@ impl Renderer {
     }
 
     fn draw_particles(&mut self, framebuffer: ash::vk::Framebuffer, descriptor_set: ash::vk::DescriptorSet, world: &crate::World) {
-        let num_entities = world.entities.len();
-        let size_entity = std::mem::size_of::<crate::entity::Entity>();
+        let num_entities;
+        let vertex_buffer;
+        let size_vertex = std::mem::size_of::<ParticleVertex>();
         unsafe {
-            let data_u8_ptr = std::mem::transmute::<*const crate::entity::Entity, *const u8>(world.entities.as_ptr());
-            let data_u8 = std::slice::from_raw_parts(data_u8_ptr, num_entities * size_entity);
-            self.update_vertex_buffer(data_u8);
+            // Particles kept on the GPU by the compute backend are drawn from its own vertex buffer
+            match world.compute.as_ref().filter(|compute| compute.resident()) {
+                Some(compute) => { (vertex_buffer, num_entities) = compute.vertex_buffer(); }
+                None => {
+                    num_entities = world.vertices.len();
+                    let data_u8_ptr = std::mem::transmute::<*const ParticleVertex, *const u8>(world.vertices.as_ptr());
+                    let data_u8 = std::slice::from_raw_parts(data_u8_ptr, num_entities * size_vertex);
+                    self.update_vertex_buffer(data_u8);
+                    vertex_buffer = self.vertex_buffer;
+                }
+            }
 
             let commander_buffer_info = ash::vk::CommandBufferAllocateInfo::builder()
                 .command_pool(self.command_pool)
@@ -779,7 +1152,7 @@ impl Renderer {
                 .clear_values(&clear_values);
             self.device.raw.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, ash::vk::SubpassContents::INLINE);
             self.device.raw.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.particles_pipeline.pipeline);
-            let vertex_buffers = [self.vertex_buffer]; let offsets = [0];
+            let vertex_buffers = [vertex_buffer]; let offsets = [0];
             self.device.raw.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
             self.device.raw.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.particles_pipeline.pipeline_layout, 0, &[descriptor_set], &[]);
             self.device.raw.cmd_draw(command_buffer, num_entities as u32, 1, 0, 0);
@@ -803,21 +1176,18 @@ impl Renderer {
     }
 
     fn draw_gravity(&mut self, framebuffer: ash::vk::Framebuffer, descriptor_set: ash::vk::DescriptorSet, world: &crate::World) {
-        if world.entities_gravity.len() == 0 { return }
-        let data: Vec<(crate::entity::Entity, f32)> = world.entities_gravity
+        if world.entities_gravity.is_empty() { return }
+        let data: Vec<GravityVertex> = world.entities_gravity
             .iter()
             .map(|g| {
-                let entity = g.inner.clone();
-                let gravity_force = match g.gravity.compute {
-                    crate::entity::GravityCompute::Linear(f) => f,
-                    crate::entity::GravityCompute::Square(f) => f
-                };
-                (entity, gravity_force)
+                let vertex = ParticleVertex::new(&g.inner, world.interpolated_position(&g.inner), world.position_camera);
+                let gravity_force = g.gravity.compute.get_force();
+                GravityVertex { vertex, force: gravity_force, law: g.gravity.compute.index() as u32 }
             })
             .collect();
 
-        let data_u8_ptr = unsafe { std::mem::transmute::<*const (crate::entity::Entity, f32), *const u8>(data.as_ptr()) };
-        let data_u8 = unsafe { std::slice::from_raw_parts(data_u8_ptr, data.len() * std::mem::size_of::<(crate::entity::Entity, f32)>()) };
+        let data_u8_ptr = unsafe { std::mem::transmute::<*const GravityVertex, *const u8>(data.as_ptr()) };
+        let data_u8 = unsafe { std::slice::from_raw_parts(data_u8_ptr, data.len() * std::mem::size_of::<GravityVertex>()) };
         self.update_vertex_buffer(data_u8);
 
         unsafe {
@@ -869,6 +1239,136 @@ impl Renderer {
         }
     }
 
+    fn draw_obstacles(&mut self, framebuffer: ash::vk::Framebuffer, descriptor_set: ash::vk::DescriptorSet, world: &crate::World) {
+        if world.obstacles.is_empty() { return }
+        let mut triangles = Vec::new();
+        world.obstacles.iter().for_each(|obstacle| obstacle.triangles(&mut triangles));
+        if triangles.is_empty() { return }
+        let data: Vec<euclid::default::Point2D<i32>> = triangles.iter().map(|vertex| relative_position(*vertex, world.position_camera)).collect();
+
+        let data_u8_ptr = unsafe { std::mem::transmute::<*const euclid::default::Point2D<i32>, *const u8>(data.as_ptr()) };
+        let data_u8 = unsafe { std::slice::from_raw_parts(data_u8_ptr, data.len() * std::mem::size_of::<euclid::default::Point2D<i32>>()) };
+        self.update_vertex_buffer(data_u8);
+
+        unsafe {
+            let commander_buffer_info = ash::vk::CommandBufferAllocateInfo::builder()
+                .command_pool(self.command_pool)
+                .level(ash::vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let command_buffer = self.device.raw.allocate_command_buffers(&commander_buffer_info).unwrap()[0];
+            let command_buffer_begin_info = ash::vk::CommandBufferBeginInfo::default();
+            self.device.raw.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+            let clear_values = [ash::vk::ClearValue {
+                color: ash::vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                }
+            }];
+            let render_pass_begin_info = ash::vk::RenderPassBeginInfo::builder()
+                .render_pass(self.obstacle_pipeline.render_pass)
+                .framebuffer(framebuffer)
+                .render_area(ash::vk::Rect2D {
+                    offset: ash::vk::Offset2D {
+                        x: 0,
+                        y: 0,
+                    },
+                    extent: self.swapchain.extent,
+                })
+                .clear_values(&clear_values);
+            self.device.raw.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, ash::vk::SubpassContents::INLINE);
+            self.device.raw.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.obstacle_pipeline.pipeline);
+            let vertex_buffers = [self.vertex_buffer]; let offsets = [0];
+            self.device.raw.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
+            self.device.raw.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.obstacle_pipeline.pipeline_layout, 0, &[descriptor_set], &[]);
+            self.device.raw.cmd_draw(command_buffer, data.len() as u32, 1, 0, 0);
+            self.device.raw.cmd_end_render_pass(command_buffer);
+
+            self.device.raw.end_command_buffer(command_buffer).unwrap();
+
+            let command_buffers = [command_buffer];
+            let submit_info = ash::vk::SubmitInfo::builder()
+                .command_buffers(&command_buffers)
+                .build();
+            let submit_infos = [submit_info];
+            let graphic_queue = self.device.raw.get_device_queue(self.device.graphic_queue.family_index, 0);
+
+            let fence = self.device.raw.create_fence(&ash::vk::FenceCreateInfo::default(), None).unwrap();
+            let fences = [fence];
+            self.device.raw.queue_submit(graphic_queue, &submit_infos, fence).unwrap();
+            self.device.raw.wait_for_fences(&fences, true, u64::MAX).unwrap();
+            self.device.raw.destroy_fence(fence, None);
+        }
+    }
+
+    fn draw_bonds(&mut self, framebuffer: ash::vk::Framebuffer, descriptor_set: ash::vk::DescriptorSet, world: &crate::World) {
+        // Bonds only move with the CPU step, particles kept by the compute backend are not drawn with them
+        if world.bonds.is_empty() || world.compute.as_ref().is_some_and(|compute| compute.resident()) { return }
+        let half_field = (world.size_field.0 / 2, world.size_field.1 / 2);
+        let wrap = (world.settings.boundary_x == crate::world::Boundary::Wrap, world.settings.boundary_y == crate::world::Boundary::Wrap);
+        let mut data: Vec<euclid::default::Point2D<i32>> = Vec::with_capacity(world.bonds.len() * 2);
+        for bond in world.bonds.iter() {
+            let (a, b) = (&world.entities[bond.a], &world.entities[bond.b]);
+            // Bonds across a wrapped edge would be drawn through the whole field
+            let delta = b.position - a.position;
+            if (wrap.0 && delta.x.abs() > half_field.0) || (wrap.1 && delta.y.abs() > half_field.1) { continue }
+            for entity in [a, b] {
+                data.push(relative_position(world.interpolated_position(entity), world.position_camera));
+            }
+        }
+        if data.is_empty() { return }
+
+        let data_u8_ptr = unsafe { std::mem::transmute::<*const euclid::default::Point2D<i32>, *const u8>(data.as_ptr()) };
+        let data_u8 = unsafe { std::slice::from_raw_parts(data_u8_ptr, data.len() * std::mem::size_of::<euclid::default::Point2D<i32>>()) };
+        self.update_vertex_buffer(data_u8);
+
+        unsafe {
+            let commander_buffer_info = ash::vk::CommandBufferAllocateInfo::builder()
+                .command_pool(self.command_pool)
+                .level(ash::vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let command_buffer = self.device.raw.allocate_command_buffers(&commander_buffer_info).unwrap()[0];
+            let command_buffer_begin_info = ash::vk::CommandBufferBeginInfo::default();
+            self.device.raw.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+            let clear_values = [ash::vk::ClearValue {
+                color: ash::vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                }
+            }];
+            let render_pass_begin_info = ash::vk::RenderPassBeginInfo::builder()
+                .render_pass(self.bond_pipeline.render_pass)
+                .framebuffer(framebuffer)
+                .render_area(ash::vk::Rect2D {
+                    offset: ash::vk::Offset2D {
+                        x: 0,
+                        y: 0,
+                    },
+                    extent: self.swapchain.extent,
+                })
+                .clear_values(&clear_values);
+            self.device.raw.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, ash::vk::SubpassContents::INLINE);
+            self.device.raw.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.bond_pipeline.pipeline);
+            let vertex_buffers = [self.vertex_buffer]; let offsets = [0];
+            self.device.raw.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
+            self.device.raw.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.bond_pipeline.pipeline_layout, 0, &[descriptor_set], &[]);
+            self.device.raw.cmd_draw(command_buffer, data.len() as u32, 1, 0, 0);
+            self.device.raw.cmd_end_render_pass(command_buffer);
+
+            self.device.raw.end_command_buffer(command_buffer).unwrap();
+
+            let command_buffers = [command_buffer];
+            let submit_info = ash::vk::SubmitInfo::builder()
+                .command_buffers(&command_buffers)
+                .build();
+            let submit_infos = [submit_info];
+            let graphic_queue = self.device.raw.get_device_queue(self.device.graphic_queue.family_index, 0);
+
+            let fence = self.device.raw.create_fence(&ash::vk::FenceCreateInfo::default(), None).unwrap();
+            let fences = [fence];
+            self.device.raw.queue_submit(graphic_queue, &submit_infos, fence).unwrap();
+            self.device.raw.wait_for_fences(&fences, true, u64::MAX).unwrap();
+            self.device.raw.destroy_fence(fence, None);
+        }
+    }
+
     pub fn draw_gui(&mut self, draw_data: &imgui::DrawData) {
         let commander_buffer_info = ash::vk::CommandBufferAllocateInfo::builder()
             .command_pool(self.command_pool)
@@ -946,10 +1446,12 @@ impl Renderer {
 
     pub fn resize(&mut self, imgui: &mut imgui::Context, size: winit::dpi::PhysicalSize<u32>) {
         unsafe { self.device.raw.device_wait_idle().unwrap(); }
-        unsafe { self.imgui_renderer.destroy(&&self.device); }
+        unsafe { self.imgui_renderer.destroy(&self.device); }
         
         unsafe {
             self.gravity_pipeline.destroy(&self.device);
+            self.obstacle_pipeline.destroy(&self.device);
+            self.bond_pipeline.destroy(&self.device);
             self.particles_pipeline.destroy(&self.device);
             self.swapchain.destroy_image_views(&self.device);
             self.swapchain.destroy();
@@ -967,6 +1469,8 @@ impl Renderer {
             
             self.particles_pipeline = ParticlesPipeline::create(&self.device, &self.swapchain, size);
             self.gravity_pipeline = GravityPipeline::create(&self.device, &self.swapchain, size);
+            self.obstacle_pipeline = ObstaclePipeline::create(&self.device, &self.swapchain, size);
+            self.bond_pipeline = BondPipeline::create(&self.device, &self.swapchain, size);
         }
     }
 
@@ -975,6 +1479,8 @@ impl Renderer {
         unsafe { self.imgui_renderer.destroy(&self.device); }
 
         self.gravity_pipeline.destroy(&self.device);
+        self.obstacle_pipeline.destroy(&self.device);
+        self.bond_pipeline.destroy(&self.device);
         self.particles_pipeline.destroy(&self.device);
 
         unsafe {
@@ -0,0 +1,410 @@
+use crate::{Coordinate, PRECISION};
+use crate::entity::{Entity, GravityCompute, GravityEntity, Integrator};
+use crate::force_field::ForceField;
+use crate::world::{Boundary, Settings};
+
+use euclid::default::{Point2D, Vector2D};
+use wide::{CmpEq, CmpGt, f32x8};
+
+const LANES: usize = 8;
+
+fn load(values: &[f32], block: usize) -> f32x8 {
+    let lanes: [f32; LANES] = values[block * LANES..(block + 1) * LANES].try_into().unwrap();
+    f32x8::from(lanes)
+}
+
+// Shortest difference across a wrapped axis, the lanes hold differences within one field size
+fn wrap(direction: f32x8, size: Coordinate) -> f32x8 {
+    let size = size as f32;
+    let shifted = direction - direction.cmp_gt(f32x8::ZERO).blend(f32x8::splat(size), f32x8::splat(-size));
+    direction.abs().cmp_gt(f32x8::splat(size * 0.5)).blend(shifted, direction)
+}
+
+fn add(values: &mut [f32], block: usize, lanes: f32x8) {
+    let sum = load(values, block) + lanes;
+    values[block * LANES..(block + 1) * LANES].copy_from_slice(&<[f32; LANES]>::from(sum));
+}
+
+// Force law of a source with its constants folded in, so the kernels only branch once per block
+#[derive(Clone, Copy)]
+pub enum Law {
+    Linear(f32),
+    Square(f32),
+    Softened { force: f32, softening_squared: f32 },
+    InverseCube(f32),
+    Spring { force: f32, rest_length: f32 },
+    Gaussian { force: f32, width_squared: f32 },
+    Vortex(f32),
+}
+
+impl Law {
+    fn new(compute: &GravityCompute, settings: &Settings) -> Law {
+        let precision = PRECISION as f64;
+        let scale = precision.powi(2) * settings.gravity_power as f64;
+        match *compute {
+            GravityCompute::Linear(p) => { Law::Linear((scale * p as f64 * 10.0) as f32) }
+            GravityCompute::Square(p) => { Law::Square((scale * p as f64 * 1_000_000.0) as f32) }
+            GravityCompute::Softened { force, softening } => {
+                Law::Softened { force: (scale * force as f64 * 1_000_000.0) as f32, softening_squared: (softening as f64 * precision).powi(2) as f32 }
+            }
+            GravityCompute::InverseCube(p) => { Law::InverseCube((scale * p as f64 * 100_000_000.0 * precision) as f32) }
+            GravityCompute::Spring { force, rest_length } => {
+                Law::Spring { force: (scale * force as f64 * 0.000_000_001) as f32, rest_length: (rest_length as f64 * precision) as f32 }
+            }
+            GravityCompute::Gaussian { force, width } => {
+                let width = width as f64 * precision;
+                Law::Gaussian { force: (scale * force as f64 * 0.001 / width) as f32, width_squared: (2.0 * width * width) as f32 }
+            }
+            GravityCompute::Vortex(p) => { Law::Vortex((scale * p as f64 * 10.0) as f32) }
+        }
+    }
+
+    // Index of the law in GravityCompute::NAMES with its two folded constants, for the compute shader
+    pub fn parameters(&self) -> (u32, f32, f32) {
+        match *self {
+            Law::Linear(force) => { (0, force, 0.0) }
+            Law::Square(force) => { (1, force, 0.0) }
+            Law::Softened { force, softening_squared } => { (2, force, softening_squared) }
+            Law::InverseCube(force) => { (3, force, 0.0) }
+            Law::Spring { force, rest_length } => { (4, force, rest_length) }
+            Law::Gaussian { force, width_squared } => { (5, force, width_squared) }
+            Law::Vortex(force) => { (6, force, 0.0) }
+        }
+    }
+
+    fn magnitude(&self, distance: f32x8, distance_squared: f32x8) -> f32x8 {
+        match *self {
+            Law::Linear(force) | Law::Vortex(force) => { f32x8::splat(force) / distance }
+            Law::Square(force) => { f32x8::splat(force) / distance_squared }
+            Law::Softened { force, softening_squared } => {
+                let inverse = f32x8::ONE / (distance_squared + f32x8::splat(softening_squared)).sqrt();
+                f32x8::splat(force) * distance * inverse * inverse * inverse
+            }
+            Law::InverseCube(force) => { f32x8::splat(force) / (distance_squared * distance) }
+            Law::Spring { force, rest_length } => { f32x8::splat(force) * (distance - f32x8::splat(rest_length)) }
+            Law::Gaussian { force, width_squared } => { f32x8::splat(force) * distance * (-distance_squared / f32x8::splat(width_squared)).exp() }
+        }
+    }
+
+    fn potential(&self, distance: f32x8, distance_squared: f32x8) -> f32x8 {
+        match *self {
+            Law::Linear(force) => { f32x8::splat(force) * distance.ln() }
+            Law::Square(force) => { -f32x8::splat(force) / distance }
+            Law::Softened { force, softening_squared } => { -f32x8::splat(force) / (distance_squared + f32x8::splat(softening_squared)).sqrt() }
+            Law::InverseCube(force) => { -f32x8::splat(force) / (distance_squared * f32x8::splat(2.0)) }
+            Law::Spring { force, rest_length } => {
+                let stretch = distance - f32x8::splat(rest_length);
+                f32x8::splat(force * 0.5) * stretch * stretch
+            }
+            Law::Gaussian { force, width_squared } => { -f32x8::splat(force * width_squared * 0.5) * (-distance_squared / f32x8::splat(width_squared)).exp() }
+            Law::Vortex(_) => { f32x8::ZERO }
+        }
+    }
+}
+
+// A gravity entity prepared for the kernels
+#[derive(Clone, Copy)]
+pub struct Source {
+    pub position: Point2D<Coordinate>,
+    pub law: Law,
+    pub cutoff: f32,
+    pub cutoff_potential: f32,
+    pub max_acceleration: f32,
+    pub charge: f32,
+    pub charge_softening_squared: f32,
+    pub reaction: bool,
+}
+
+impl Source {
+    pub fn new(index: usize, gravity: &GravityEntity, num_gravities: usize, settings: &Settings) -> Source {
+        let precision = PRECISION as f64;
+        let law = Law::new(&gravity.gravity.compute, settings);
+        let cutoff = gravity.gravity.cutoff.map_or(f32::INFINITY, |cutoff| cutoff * PRECISION as f32);
+        let cutoff_potential = if cutoff.is_finite() { law.potential(f32x8::splat(cutoff), f32x8::splat(cutoff * cutoff)).reduce_add() / LANES as f32 } else { 0.0 };
+        Source {
+            position: gravity.inner.position,
+            law,
+            cutoff,
+            cutoff_potential,
+            max_acceleration: settings.max_gravity_speed * PRECISION as f32,
+            charge: (settings.charge_strength as f64 * gravity.inner.charge as f64 * precision.powi(3)) as f32,
+            charge_softening_squared: (settings.charge_softening as f64 * precision).powi(2) as f32,
+            reaction: settings.coupling && index < num_gravities && gravity.movable,
+        }
+    }
+}
+
+// Structure of arrays copy of a chunk of particles for the SIMD kernels, gathered from the entities and written back every step, padded to whole blocks of LANES
+#[derive(Clone, Default)]
+pub struct Particles {
+    len: usize,
+    pub x: Vec<Coordinate>,
+    pub y: Vec<Coordinate>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub charge_to_mass: Vec<f32>,
+    pub mass: Vec<f32>,
+    extra_x: Vec<f32>,
+    extra_y: Vec<f32>,
+    sample_x: Vec<Coordinate>,
+    sample_y: Vec<Coordinate>,
+    ax: Vec<f32>,
+    ay: Vec<f32>,
+    stage_x: Vec<f32>,
+    stage_y: Vec<f32>,
+    new_vx: Vec<f32>,
+    new_vy: Vec<f32>,
+    displacement_x: Vec<f32>,
+    displacement_y: Vec<f32>,
+    potential: Vec<f32>,
+}
+
+impl Particles {
+    fn blocks(&self) -> usize {
+        self.x.len() / LANES
+    }
+
+    pub fn gather(&mut self, entities: &[Entity], extra_accelerations: &[Vector2D<f32>]) {
+        self.len = entities.len();
+        let padded = self.len.div_ceil(LANES) * LANES;
+        for values in [&mut self.x, &mut self.y, &mut self.sample_x, &mut self.sample_y] { values.resize(padded, 0); }
+        for values in [&mut self.vx, &mut self.vy, &mut self.charge_to_mass, &mut self.mass, &mut self.extra_x, &mut self.extra_y, &mut self.ax, &mut self.ay,
+            &mut self.stage_x, &mut self.stage_y, &mut self.new_vx, &mut self.new_vy, &mut self.displacement_x, &mut self.displacement_y, &mut self.potential] {
+            values.clear();
+            values.resize(padded, 0.0);
+        }
+
+        for (i, entity) in entities.iter().enumerate() {
+            let extra = extra_accelerations.get(i).copied().unwrap_or(Vector2D::zero());
+            self.x[i] = entity.position.x;
+            self.y[i] = entity.position.y;
+            self.vx[i] = entity.speed.x;
+            self.vy[i] = entity.speed.y;
+            self.charge_to_mass[i] = entity.charge_to_mass();
+            self.mass[i] = entity.mass;
+            self.extra_x[i] = extra.x;
+            self.extra_y[i] = extra.y;
+        }
+    }
+
+    // Samples the accelerations at the positions shifted by offset * factor
+    fn sample(&mut self, offset: Option<(&[f32], &[f32])>, factor: f32) {
+        match offset {
+            Some((offset_x, offset_y)) => {
+                for i in 0..self.x.len() {
+                    self.sample_x[i] = self.x[i] + (offset_x[i] * factor).round() as Coordinate;
+                    self.sample_y[i] = self.y[i] + (offset_y[i] * factor).round() as Coordinate;
+                }
+            }
+            None => {
+                self.sample_x.copy_from_slice(&self.x);
+                self.sample_y.copy_from_slice(&self.y);
+            }
+        }
+    }
+
+    // Same result as Entity::direction_to, only the lanes sitting exactly on the source take the scalar path for their random direction
+    fn direction(&self, block: usize, source: Point2D<Coordinate>, settings: &Settings, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) -> (f32x8, f32x8) {
+        let range = block * LANES..(block + 1) * LANES;
+        let mut direction_x = [0.0; LANES];
+        let mut direction_y = [0.0; LANES];
+        for (lane, (x, y)) in self.sample_x[range.clone()].iter().zip(&self.sample_y[range]).enumerate() {
+            direction_x[lane] = (source.x - x) as f32;
+            direction_y[lane] = (source.y - y) as f32;
+        }
+        let (mut x, mut y) = (f32x8::from(direction_x), f32x8::from(direction_y));
+        if settings.boundary_x == Boundary::Wrap { x = wrap(x, size_field.0); }
+        if settings.boundary_y == Boundary::Wrap { y = wrap(y, size_field.1); }
+
+        if (x.cmp_eq(f32x8::ZERO) & y.cmp_eq(f32x8::ZERO)).any() {
+            (direction_x, direction_y) = (x.into(), y.into());
+            for lane in 0..LANES {
+                if direction_x[lane] != 0.0 || direction_y[lane] != 0.0 { continue }
+                let i = block * LANES + lane;
+                let direction = Entity::direction_to(Point2D::new(self.sample_x[i], self.sample_y[i]), source, settings, size_field, rng);
+                (direction_x[lane], direction_y[lane]) = (direction.x as f32, direction.y as f32);
+            }
+            (x, y) = (f32x8::from(direction_x), f32x8::from(direction_y));
+        }
+        (x, y)
+    }
+
+    fn accelerations(&mut self, sources: &[Source], mut reactions: Option<&mut [Vector2D<f32>]>, fields: &[ForceField], settings: &Settings, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) {
+        self.ax.copy_from_slice(&self.extra_x);
+        self.ay.copy_from_slice(&self.extra_y);
+
+        for (index, source) in sources.iter().enumerate() {
+            let max = f32x8::splat(source.max_acceleration);
+            let cutoff = f32x8::splat(source.cutoff);
+            let mut reaction = (f32x8::ZERO, f32x8::ZERO);
+            for block in 0..self.blocks() {
+                let (direction_x, direction_y) = self.direction(block, source.position, settings, size_field, rng);
+                let distance_squared = direction_x * direction_x + direction_y * direction_y;
+                let distance = distance_squared.sqrt();
+                let (unit_x, unit_y) = (direction_x / distance, direction_y / distance);
+
+                let magnitude = source.law.magnitude(distance, distance_squared).max(-max).min(max);
+                let magnitude = distance.cmp_gt(cutoff).blend(f32x8::ZERO, magnitude);
+                let (mut acceleration_x, mut acceleration_y) = match source.law {
+                    Law::Vortex(_) => { (-unit_y * magnitude, unit_x * magnitude) }
+                    _ => { (unit_x * magnitude, unit_y * magnitude) }
+                };
+                if source.charge != 0.0 {
+                    let charge = -f32x8::splat(source.charge) * load(&self.charge_to_mass, block) / (distance_squared + f32x8::splat(source.charge_softening_squared));
+                    let charge = charge.max(-max).min(max);
+                    acceleration_x += unit_x * charge;
+                    acceleration_y += unit_y * charge;
+                }
+
+                add(&mut self.ax, block, acceleration_x);
+                add(&mut self.ay, block, acceleration_y);
+                if source.reaction {
+                    let mass = load(&self.mass, block);
+                    reaction.0 += acceleration_x * mass;
+                    reaction.1 += acceleration_y * mass;
+                }
+            }
+            if let Some(reactions) = reactions.as_deref_mut() {
+                if source.reaction { reactions[index] -= Vector2D::new(reaction.0.reduce_add(), reaction.1.reduce_add()); }
+            }
+        }
+
+        if !fields.is_empty() || settings.boundary_x == Boundary::Soft || settings.boundary_y == Boundary::Soft {
+            for i in 0..self.len {
+                let position = Point2D::new(self.sample_x[i], self.sample_y[i]);
+                let acceleration = fields.iter().map(|field| field.acceleration(position, settings, size_field, rng)).sum::<Vector2D<f32>>()
+                    + Entity::wall_acceleration(position, settings, size_field);
+                self.ax[i] += acceleration.x;
+                self.ay[i] += acceleration.y;
+            }
+        }
+    }
+
+    // Same integrators as Entity::integrate, each stage evaluates the accelerations of the whole chunk
+    #[allow(clippy::too_many_arguments)]
+    pub fn integrate(&mut self, entities: &mut [Entity], sources: &[Source], reactions: Option<&mut [Vector2D<f32>]>, fields: &[ForceField], settings: &Settings, elapsed: f32, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) {
+        let len = self.x.len();
+        match settings.integrator {
+            Integrator::SemiImplicitEuler => {
+                self.sample(None, 0.0);
+                self.accelerations(sources, reactions, fields, settings, size_field, rng);
+                for i in 0..len {
+                    self.new_vx[i] = self.vx[i] + self.ax[i] * elapsed;
+                    self.new_vy[i] = self.vy[i] + self.ay[i] * elapsed;
+                }
+                for (i, entity) in entities.iter_mut().enumerate() {
+                    entity.speed = Vector2D::new(self.new_vx[i], self.new_vy[i]);
+                    entity.update_position(settings, elapsed, size_field, rng);
+                }
+                return
+            }
+            Integrator::Leapfrog => {
+                // The back reaction is taken at the current positions, like the scalar path
+                if reactions.is_some() {
+                    self.sample(None, 0.0);
+                    self.accelerations(sources, reactions, fields, settings, size_field, rng);
+                }
+                let (vx, vy) = (std::mem::take(&mut self.vx), std::mem::take(&mut self.vy));
+                self.sample(Some((&vx, &vy)), elapsed * 0.5);
+                (self.vx, self.vy) = (vx, vy);
+                self.accelerations(sources, None, fields, settings, size_field, rng);
+                for i in 0..len {
+                    self.new_vx[i] = self.vx[i] + self.ax[i] * elapsed;
+                    self.new_vy[i] = self.vy[i] + self.ay[i] * elapsed;
+                    self.displacement_x[i] = (self.vx[i] + self.new_vx[i]) * elapsed * 0.5;
+                    self.displacement_y[i] = (self.vy[i] + self.new_vy[i]) * elapsed * 0.5;
+                }
+            }
+            Integrator::VelocityVerlet => {
+                self.sample(None, 0.0);
+                self.accelerations(sources, reactions, fields, settings, size_field, rng);
+                for i in 0..len {
+                    self.new_vx[i] = self.vx[i] + self.ax[i] * elapsed * 0.5;
+                    self.new_vy[i] = self.vy[i] + self.ay[i] * elapsed * 0.5;
+                    self.displacement_x[i] = self.new_vx[i] * elapsed;
+                    self.displacement_y[i] = self.new_vy[i] * elapsed;
+                }
+                let (displacement_x, displacement_y) = (std::mem::take(&mut self.displacement_x), std::mem::take(&mut self.displacement_y));
+                self.sample(Some((&displacement_x, &displacement_y)), 1.0);
+                (self.displacement_x, self.displacement_y) = (displacement_x, displacement_y);
+                self.accelerations(sources, None, fields, settings, size_field, rng);
+                for i in 0..len {
+                    self.new_vx[i] += self.ax[i] * elapsed * 0.5;
+                    self.new_vy[i] += self.ay[i] * elapsed * 0.5;
+                }
+            }
+            Integrator::Rk4 => {
+                // stage_x/y hold the position derivative of the last stage, ax/ay its speed derivative
+                self.sample(None, 0.0);
+                self.accelerations(sources, reactions, fields, settings, size_field, rng);
+                self.stage_x.copy_from_slice(&self.vx);
+                self.stage_y.copy_from_slice(&self.vy);
+                self.new_vx.copy_from_slice(&self.ax);
+                self.new_vy.copy_from_slice(&self.ay);
+                self.displacement_x.copy_from_slice(&self.vx);
+                self.displacement_y.copy_from_slice(&self.vy);
+                for (factor, weight) in [(0.5, 2.0), (0.5, 2.0), (1.0, 1.0)] {
+                    let (stage_x, stage_y) = (std::mem::take(&mut self.stage_x), std::mem::take(&mut self.stage_y));
+                    self.sample(Some((&stage_x, &stage_y)), elapsed * factor);
+                    (self.stage_x, self.stage_y) = (stage_x, stage_y);
+                    for i in 0..len {
+                        self.stage_x[i] = self.vx[i] + self.ax[i] * elapsed * factor;
+                        self.stage_y[i] = self.vy[i] + self.ay[i] * elapsed * factor;
+                    }
+                    self.accelerations(sources, None, fields, settings, size_field, rng);
+                    for i in 0..len {
+                        self.new_vx[i] += self.ax[i] * weight;
+                        self.new_vy[i] += self.ay[i] * weight;
+                        self.displacement_x[i] += self.stage_x[i] * weight;
+                        self.displacement_y[i] += self.stage_y[i] * weight;
+                    }
+                }
+                for i in 0..len {
+                    self.new_vx[i] = self.vx[i] + self.new_vx[i] * elapsed / 6.0;
+                    self.new_vy[i] = self.vy[i] + self.new_vy[i] * elapsed / 6.0;
+                    self.displacement_x[i] *= elapsed / 6.0;
+                    self.displacement_y[i] *= elapsed / 6.0;
+                }
+            }
+        }
+
+        for (i, entity) in entities.iter_mut().enumerate() {
+            let speed = Vector2D::new(self.new_vx[i], self.new_vy[i]);
+            let displacement = Vector2D::new(self.displacement_x[i], self.displacement_y[i]);
+            entity.advance(speed, displacement, settings, elapsed, size_field, rng);
+        }
+    }
+
+    // Largest mean acceleration of the last integrate, see Entity::integrate
+    pub fn max_acceleration(&self, elapsed: f32) -> f32 {
+        if elapsed <= 0.0 { return 0.0 }
+        (0..self.len).map(|i| (self.new_vx[i] - self.vx[i]).hypot(self.new_vy[i] - self.vy[i])).fold(0.0, f32::max) / elapsed
+    }
+
+    // Potential energy of every particle at its current position, see Entity::potential_energy
+    pub fn potential_energies(&mut self, entities: &[Entity], sources: &[Source], settings: &Settings, size_field: (Coordinate, Coordinate), rng: &fastrand::Rng) -> &[f32] {
+        for (i, entity) in entities.iter().enumerate() {
+            self.sample_x[i] = entity.position.x;
+            self.sample_y[i] = entity.position.y;
+        }
+        self.potential.iter_mut().for_each(|potential| *potential = 0.0);
+
+        for source in sources.iter() {
+            let cutoff = f32x8::splat(source.cutoff);
+            for block in 0..self.blocks() {
+                let (direction_x, direction_y) = self.direction(block, source.position, settings, size_field, rng);
+                let distance_squared = direction_x * direction_x + direction_y * direction_y;
+                let distance = distance_squared.sqrt();
+
+                let potential = source.law.potential(distance, distance_squared) - f32x8::splat(source.cutoff_potential);
+                let mut potential = distance.cmp_gt(cutoff).blend(f32x8::ZERO, potential);
+                if source.charge != 0.0 {
+                    potential += f32x8::splat(source.charge) * load(&self.charge_to_mass, block) / (distance_squared + f32x8::splat(source.charge_softening_squared)).sqrt();
+                }
+                let mass = load(&self.mass, block);
+                add(&mut self.potential, block, potential * mass);
+            }
+        }
+        &self.potential[..self.len]
+    }
+}
\ No newline at end of file
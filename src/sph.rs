@@ -0,0 +1,71 @@
+use crate::PRECISION;
+use crate::entity::Entity;
+use crate::grid::Grid;
+use crate::threadpool::Threadpool;
+use crate::world::Settings;
+
+use euclid::default::Vector2D;
+
+// 2D kernels of Müller et al., distances in pixels and unit mass per entity
+fn poly6(distance_squared: f32, h: f32) -> f32 {
+    let x = h * h - distance_squared;
+    if x <= 0.0 { return 0.0 }
+    4.0 / (std::f32::consts::PI * h.powi(8)) * x * x * x
+}
+
+fn spiky_gradient(distance: f32, h: f32) -> f32 {
+    if distance >= h { return 0.0 }
+    -30.0 / (std::f32::consts::PI * h.powi(5)) * (h - distance) * (h - distance)
+}
+
+fn viscosity_laplacian(distance: f32, h: f32) -> f32 {
+    if distance >= h { return 0.0 }
+    40.0 / (std::f32::consts::PI * h.powi(5)) * (h - distance)
+}
+
+pub fn update_densities(entities: &[Entity], grid: &Grid, settings: &Settings, densities: &mut Vec<f32>, threadpool: &mut Threadpool) {
+    densities.resize(entities.len(), 0.0);
+    let h = settings.sph_smoothing_length;
+    let radius = h * PRECISION as f32;
+    let scale = (PRECISION as f32).powi(2).recip();
+
+    threadpool.for_each_chunk_mut(densities, |_chunk, offset, densities| {
+        for (i, density) in densities.iter_mut().enumerate() {
+            *density = 0.0;
+            grid.for_each_neighbour(entities, entities[offset + i].position, radius, |_other, _direction, distance_squared| {
+                *density += poly6(distance_squared * scale, h);
+            });
+        }
+    });
+}
+
+pub fn update_accelerations(entities: &[Entity], grid: &Grid, settings: &Settings, densities: &[f32], accelerations: &mut Vec<Vector2D<f32>>, threadpool: &mut Threadpool) {
+    accelerations.resize(entities.len(), Vector2D::zero());
+    let h = settings.sph_smoothing_length;
+    let radius = h * PRECISION as f32;
+    let precision = PRECISION as f32;
+    let pressure = |density: f32| settings.stiffness * (density - settings.rest_density).max(0.0);
+
+    threadpool.for_each_chunk_mut(accelerations, |_chunk, offset, accelerations| {
+        for (i, acceleration) in accelerations.iter_mut().enumerate() {
+            let index = offset + i;
+            let entity = &entities[index];
+            let density = densities[index];
+            let pressure_entity = pressure(density);
+
+            let mut force_pressure = Vector2D::zero();
+            let mut force_viscosity = Vector2D::zero();
+            grid.for_each_neighbour(entities, entity.position, radius, |other, direction, distance_squared| {
+                if other == index || distance_squared == 0.0 { return }
+                let distance = distance_squared.sqrt() / precision;
+                let normal = direction / (distance * precision);
+                let density_other = densities[other];
+
+                force_pressure += normal * ((pressure_entity + pressure(density_other)) / (2.0 * density_other) * spiky_gradient(distance, h));
+                force_viscosity += (entities[other].speed - entity.speed) / precision * (viscosity_laplacian(distance, h) / density_other);
+            });
+
+            *acceleration = (force_pressure + force_viscosity * settings.viscosity) / density.max(f32::EPSILON) * precision;
+        }
+    });
+}
\ No newline at end of file
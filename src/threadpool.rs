@@ -21,8 +21,8 @@ impl Threadpool {
     pub fn new_with_threads(num: usize) -> Threadpool {
         let (sender, receiver) = crossbeam_channel::unbounded();
         let mut threadpool = Threadpool {
-            sender: sender,
-            receiver: receiver,
+            sender,
+            receiver,
             threads: HashMap::new(),
             threads_to_close: crossbeam_channel::unbounded(),
         };
@@ -34,7 +34,7 @@ impl Threadpool {
             let a:i32;
         }
 
-        return threadpool;
+        threadpool
     }
 
     pub fn num_threads(&self) -> usize {
@@ -46,15 +46,11 @@ impl Threadpool {
         let send_close = self.threads_to_close.0.clone();
         let thread = std::thread::spawn(move || {
             loop {
-                let maybe_work = work_receiver.try_recv();
-                match maybe_work {
-                    Ok(work) => {   
-                        match work {
-                            Work::Close => { send_close.send(std::thread::current().id()).unwrap(); break }
-                            Work::Closure(f) => { f(); }
-                        }
-                    },
-                    Err(_) => {}
+                if let Ok(work) = work_receiver.try_recv() {
+                    match work {
+                        Work::Close => { send_close.send(std::thread::current().id()).unwrap(); break }
+                        Work::Closure(f) => { f(); }
+                    }
                 }
             }
         });
@@ -78,12 +74,45 @@ impl Threadpool {
     }
     
     pub unsafe fn send_work_unsafe_boxed<'a>(&mut self, f: Box<dyn FnOnce() + 'a + Send + Sync>) {
-        let f = std::mem::transmute(f);
+        let f = std::mem::transmute::<Box<dyn FnOnce() + 'a + Send + Sync>, Box<dyn FnOnce() + Send + Sync>>(f);
         self.sender.send(Work::Closure(f)).unwrap();
     }
     
     pub unsafe fn send_work_unsafe<F: FnOnce() + Send + Sync>(&mut self, f: F) {
-        self.send_work_unsafe_boxed(Box::new(move || f()));
+        self.send_work_unsafe_boxed(Box::new(f));
+    }
+
+    pub fn size_chunk(&self, len: usize) -> usize {
+        len / self.num_threads() + 1
+    }
+
+    pub fn map_chunks<R: Send + Sync, F: Fn(usize, std::ops::Range<usize>) -> R + Send + Sync>(&mut self, len: usize, f: F) -> Vec<R> {
+        let size_chunk = self.size_chunk(len);
+        let mut results: Vec<Option<R>> = (0..self.num_threads()).map(|_| None).collect();
+        let f = &f;
+        for (chunk, result) in results.iter_mut().enumerate() {
+            let range = (chunk * size_chunk).min(len)..((chunk + 1) * size_chunk).min(len);
+            unsafe {
+                self.send_work_unsafe(move || {
+                    *result = Some(f(chunk, range));
+                });
+            }
+        }
+        self.wait();
+        results.into_iter().map(|result| result.unwrap()).collect()
+    }
+
+    pub fn for_each_chunk_mut<T: Send + Sync, F: Fn(usize, usize, &mut [T]) + Send + Sync>(&mut self, data: &mut [T], f: F) {
+        let size_chunk = self.size_chunk(data.len());
+        let f = &f;
+        for (chunk, slice) in data.chunks_mut(size_chunk).enumerate() {
+            unsafe {
+                self.send_work_unsafe(move || {
+                    f(chunk, chunk * size_chunk, slice);
+                });
+            }
+        }
+        self.wait();
     }
 
     pub fn wait(&mut self) {
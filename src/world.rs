@@ -1,67 +1,463 @@
+use crate::boids;
+use crate::collision;
+use crate::compute;
+use crate::diagnostics;
+use crate::diagnostics::Diagnostics;
+use crate::emitter::{Emitter, Sink};
 use crate::entity::Entity;
 use crate::entity::GravityEntity;
 use crate::entity::GravityCompute;
 use crate::entity::GravitySettings;
-use crate::PRECISION;
+use crate::entity::Integrator;
+use crate::entity::Thermostat;
+use crate::force_field::ForceField;
+use crate::generator;
+use crate::generator::Generator;
+use crate::bond;
+use crate::bond::Bond;
+use crate::obstacle;
+use crate::obstacle::Obstacle;
+use crate::{Coordinate, PRECISION, random_coordinate};
+use crate::particle_life;
+use crate::grid::Grid;
+use crate::quadtree::Quadtree;
 use crate::renderer;
+use crate::soa;
+use crate::sph;
 use crate::threadpool;
 
 use euclid::default::{Point2D, Vector2D};
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Timestep {
+    Variable,
+    Fixed,
+    Adaptive,
+}
+
+impl Timestep {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timestep::Variable => { "variable" }
+            Timestep::Fixed => { "fixed" }
+            Timestep::Adaptive => { "adaptive" }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    Cpu,
+    Gpu,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 2] = [Backend::Cpu, Backend::Gpu];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Cpu => { "cpu" }
+            Backend::Gpu => { "gpu" }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Boundary {
+    Reflect,
+    Wrap,
+    Absorb,
+    Soft,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 4] = [Boundary::Reflect, Boundary::Wrap, Boundary::Absorb, Boundary::Soft];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Reflect => { "reflect" }
+            Boundary::Wrap => { "wrap" }
+            Boundary::Absorb => { "absorb" }
+            Boundary::Soft => { "soft" }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpawnRule {
+    Random,
+    Center,
+    OppositeEdge,
+}
+
+impl SpawnRule {
+    pub const ALL: [SpawnRule; 3] = [SpawnRule::Random, SpawnRule::Center, SpawnRule::OppositeEdge];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpawnRule::Random => { "random" }
+            SpawnRule::Center => { "center" }
+            SpawnRule::OppositeEdge => { "opposite_edge" }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChargeSpawn {
+    Neutral,
+    Positive,
+    Negative,
+    RandomSign,
+    Uniform,
+}
+
+impl ChargeSpawn {
+    pub const ALL: [ChargeSpawn; 5] = [ChargeSpawn::Neutral, ChargeSpawn::Positive, ChargeSpawn::Negative, ChargeSpawn::RandomSign, ChargeSpawn::Uniform];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChargeSpawn::Neutral => { "neutral" }
+            ChargeSpawn::Positive => { "positive" }
+            ChargeSpawn::Negative => { "negative" }
+            ChargeSpawn::RandomSign => { "random_sign" }
+            ChargeSpawn::Uniform => { "uniform" }
+        }
+    }
+}
+
+pub const MAX_SPECIES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Speed,
+    Species,
+    Charge,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 3] = [ColorMode::Speed, ColorMode::Species, ColorMode::Charge];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Speed => { "speed" }
+            ColorMode::Species => { "species" }
+            ColorMode::Charge => { "charge" }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interaction {
+    None,
+    BarnesHut,
+    Sph,
+    Boids,
+    ParticleLife,
+}
+
+impl Interaction {
+    pub const ALL: [Interaction; 5] = [Interaction::None, Interaction::BarnesHut, Interaction::Sph, Interaction::Boids, Interaction::ParticleLife];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interaction::None => { "none" }
+            Interaction::BarnesHut => { "barnes_hut" }
+            Interaction::Sph => { "sph" }
+            Interaction::Boids => { "boids" }
+            Interaction::ParticleLife => { "particle_life" }
+        }
+    }
+
+    pub fn uses_grid(&self) -> bool {
+        match self {
+            Interaction::None | Interaction::BarnesHut => { false }
+            Interaction::Sph | Interaction::Boids | Interaction::ParticleLife => { true }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Settings {
+    pub seed: u64,
+    pub field_scale: f32,
     pub time_factor: f32,
+    pub timestep: Timestep,
+    pub fixed_timestep: f32,
+    pub max_substeps: u32,
+    pub adaptive_safety: f32,
+    pub adaptive_length: f32,
+    pub max_adaptive_steps: u32,
+    pub integrator: Integrator,
+    pub interaction: Interaction,
+    pub nbody_gravity: f32,
+    pub theta: f32,
+    pub softening: f32,
+    pub sph_smoothing_length: f32,
+    pub rest_density: f32,
+    pub stiffness: f32,
+    pub viscosity: f32,
+    pub perception_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub boids_speed: f32,
+    pub species_count: u32,
+    pub species_proportions: [f32; MAX_SPECIES],
+    pub species_matrix: [[f32; MAX_SPECIES]; MAX_SPECIES],
+    pub species_colors: [(f32, f32, f32); MAX_SPECIES],
+    pub life_radius: f32,
+    pub life_strength: f32,
+    pub grid_cell_size: f32,
+    pub collisions: bool,
+    pub particle_radius: f32,
+    pub restitution: f32,
+    pub mass_min: f32,
+    pub mass_max: f32,
+    pub charge_spawn: ChargeSpawn,
+    pub charge_magnitude: f32,
+    pub charge_strength: f32,
+    pub charge_softening: f32,
+    pub merging: bool,
+    pub accretion: bool,
+    pub capture_radius: f32,
+    pub accretion_force: f32,
+    pub coupling: bool,
+    pub coupling_mass_ratio: f32,
+    pub obstacle_restitution: f32,
+    pub obstacle_friction: f32,
+    pub bond_stiffness: f32,
+    pub bond_damping: f32,
+    pub bond_break_strain: f32,
+    pub bond_spacing: f32,
+    pub bond_size: u32,
     pub mouse_gravity: GravityEntity,
     pub mouse_gravity_active: bool,
     pub gravity_power: f32,
     pub energy_loss: f32,
+    pub thermostat: Thermostat,
+    pub temperature: f32,
+    pub thermostat_coupling: f32,
     pub max_speed: f32,
     pub max_gravity_speed: f32,
-    pub block: bool,
+    pub boundary_x: Boundary,
+    pub boundary_y: Boundary,
+    pub spawn_rule: SpawnRule,
+    pub wall_restitution: f32,
+    pub wall_margin: f32,
+    pub wall_repulsion: f32,
+    pub generator: Generator,
+    pub generator_gravity: u32,
+    pub generator_radius: f32,
+    pub generator_inner_radius: f32,
+    pub generator_rings: u32,
+    pub generator_sigma: f32,
+    pub generator_spacing: f32,
+    pub generator_separation: f32,
+    pub generator_speed: f32,
+    pub diagnostics: bool,
+    pub diagnostics_history: u32,
+    pub simd: bool,
+    pub backend: Backend,
     pub color_base: (f32, f32, f32),
     pub color_fast: (f32, f32, f32),
     pub color_ratio_speed: f32,
+    pub color_mode: ColorMode,
     pub alpha: f32,
 }
 
+impl Settings {
+    pub fn random_species(&self, rng: &fastrand::Rng) -> u32 {
+        let proportions = &self.species_proportions[..self.species_count as usize];
+        let total: f32 = proportions.iter().sum();
+        let mut choice = rng.f32() * total;
+        for (species, proportion) in proportions.iter().enumerate() {
+            if choice < *proportion { return species as u32 }
+            choice -= proportion;
+        }
+        self.species_count - 1
+    }
+
+    pub fn spawn_attributes(&self, entity: &mut Entity, rng: &fastrand::Rng) {
+        entity.species = self.random_species(rng);
+        entity.mass = self.mass_min + rng.f32() * (self.mass_max - self.mass_min).max(0.0);
+        entity.charge = self.charge_magnitude * match self.charge_spawn {
+            ChargeSpawn::Neutral => { 0.0 }
+            ChargeSpawn::Positive => { 1.0 }
+            ChargeSpawn::Negative => { -1.0 }
+            ChargeSpawn::RandomSign => { if rng.bool() { 1.0 } else { -1.0 } }
+            ChargeSpawn::Uniform => { rng.f32() * 2.0 - 1.0 }
+        };
+    }
+
+    pub fn randomize_species_matrix(&mut self, rng: &fastrand::Rng) {
+        for row in self.species_matrix.iter_mut() {
+            for attraction in row.iter_mut() {
+                *attraction = rng.f32() * 2.0 - 1.0;
+            }
+        }
+    }
+}
+
 impl Default for Settings {
     fn default() -> Settings {
         Settings {
+            seed: 0x5eed,
+            field_scale: 1.0,
             time_factor: 1.0,
+            timestep: Timestep::Variable,
+            fixed_timestep: 1.0 / 120.0,
+            max_substeps: 8,
+            adaptive_safety: 0.5,
+            adaptive_length: 5.0,
+            max_adaptive_steps: 64,
+            integrator: Integrator::SemiImplicitEuler,
+            interaction: Interaction::None,
+            nbody_gravity: 100.0,
+            theta: 0.7,
+            softening: 5.0,
+            sph_smoothing_length: 8.0,
+            rest_density: 0.3,
+            stiffness: 2_000.0,
+            viscosity: 1.0,
+            perception_radius: 10.0,
+            separation: 1_000.0,
+            alignment: 1.0,
+            cohesion: 1.0,
+            boids_speed: 100.0,
+            species_count: 4,
+            species_proportions: [1.0; MAX_SPECIES],
+            species_matrix: {
+                let mut matrix = [[-0.2; MAX_SPECIES]; MAX_SPECIES];
+                for (i, row) in matrix.iter_mut().enumerate() {
+                    row[i] = 1.0;
+                    row[(i + 1) % MAX_SPECIES] = 0.5;
+                }
+                matrix
+            },
+            species_colors: [
+                (1.0, 0.3, 0.3),
+                (0.3, 1.0, 0.3),
+                (0.3, 0.5, 1.0),
+                (1.0, 1.0, 0.3),
+                (1.0, 0.3, 1.0),
+                (0.3, 1.0, 1.0),
+                (1.0, 0.6, 0.2),
+                (1.0, 1.0, 1.0),
+            ],
+            life_radius: 20.0,
+            life_strength: 100.0,
+            grid_cell_size: 10.0,
+            collisions: false,
+            particle_radius: 1.0,
+            restitution: 0.8,
+            mass_min: 1.0,
+            mass_max: 1.0,
+            charge_spawn: ChargeSpawn::Neutral,
+            charge_magnitude: 1.0,
+            charge_strength: 1_000_000.0,
+            charge_softening: 5.0,
+            merging: false,
+            accretion: false,
+            capture_radius: 5.0,
+            accretion_force: 0.001,
+            coupling: false,
+            coupling_mass_ratio: 0.000_01,
+            obstacle_restitution: 0.8,
+            obstacle_friction: 0.0,
+            bond_stiffness: 200.0,
+            bond_damping: 2.0,
+            bond_break_strain: 1.0,
+            bond_spacing: 10.0,
+            bond_size: 16,
             mouse_gravity: GravityEntity {
                 gravity: GravitySettings {
-                    compute: GravityCompute::Linear(100.0)
+                    compute: GravityCompute::Linear(100.0),
+                    cutoff: None,
                 },
                 ..Default::default()
             },
             mouse_gravity_active: false,
             gravity_power: 100.0,
             energy_loss: 0.1,
+            thermostat: Thermostat::EnergyLoss,
+            temperature: 1_000.0,
+            thermostat_coupling: 1.0,
             max_speed: 10_000.0,
             max_gravity_speed: 10_000.0,
-            block: true,
+            boundary_x: Boundary::Reflect,
+            boundary_y: Boundary::Reflect,
+            spawn_rule: SpawnRule::Random,
+            wall_restitution: 0.5,
+            wall_margin: 20.0,
+            wall_repulsion: 1_000.0,
+            generator: Generator::Uniform,
+            generator_gravity: 0,
+            generator_radius: 300.0,
+            generator_inner_radius: 20.0,
+            generator_rings: 5,
+            generator_sigma: 100.0,
+            generator_spacing: 2.0,
+            generator_separation: 600.0,
+            generator_speed: 50.0,
+            diagnostics: true,
+            diagnostics_history: 1_000,
+            simd: true,
+            backend: Backend::Cpu,
             color_base: (1.0, 1.0, 1.0),
             color_fast: (1.0, 0.0, 0.0),
             color_ratio_speed: 1.0,
+            color_mode: ColorMode::Speed,
             alpha: 0.2,
         }
     }
 }
 
 pub struct World {
-    pub position_camera: Point2D<i32>,
-    pub size_field: (i32, i32),
+    pub position_camera: Point2D<Coordinate>,
+    pub size_field: (Coordinate, Coordinate),
     pub zoom: f32,
     pub debug: bool,
     pub last_update: std::time::Instant,
     pub elapsed_since_update: f32,
+    pub accumulator: f32,
+    pub interpolation: f32,
+    pub substeps: u32,
     pub entities: Vec<Entity>,
+    pub vertices: Vec<renderer::ParticleVertex>,
     pub entities_gravity: Vec<GravityEntity>,
+    pub force_fields: Vec<ForceField>,
+    pub obstacles: Vec<Obstacle>,
+    pub bonds: Vec<Bond>,
+    pub obstacle_broadphase: obstacle::Broadphase,
+    pub reaction_chunks: Vec<Vec<Vector2D<f32>>>,
+    pub diagnostic_chunks: Vec<diagnostics::Sums>,
+    pub rate_chunks: Vec<(f32, f32)>,
+    pub soa_chunks: Vec<soa::Particles>,
+    pub compute: Option<compute::Compute>,
+    pub diagnostics: Diagnostics,
+    pub diagnostics_history: std::collections::VecDeque<Diagnostics>,
+    pub emitters: Vec<Emitter>,
+    pub sinks: Vec<Sink>,
+    pub spawned: u64,
+    pub absorbed: u64,
+    pub expired: u64,
+    // Particles with a lifetime, counted by the cpu step that decreases them
+    pub num_mortal: usize,
+    pub broken_bonds: u64,
+    // Largest speed and acceleration measured by the last step, None until a step ran
+    pub max_rates: Option<(f32, f32)>,
+    pub accelerations: Vec<Vector2D<f32>>,
+    pub quadtree: Quadtree,
+    pub grid: Grid,
+    pub collision_corrections: Vec<collision::Correction>,
+    pub densities: Vec<f32>,
     pub settings: Settings,
-    pub num_particles: i32,
+    pub max_particles: i32,
     pub benchmark_update: f32,
     pub benchmark_draw: f32,
     pub threadpool: threadpool::Threadpool,
+    pub rng: fastrand::Rng,
+    pub step_count: u64,
 }
 
 impl World {
@@ -69,6 +465,13 @@ impl World {
         self.debug = !self.debug;
     }
 
+    // Seed of the random stream of one worker chunk for the current step, only depends on the world seed
+    pub fn chunk_seed(&self, chunk: usize) -> u64 {
+        self.settings.seed
+            ^ self.step_count.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (chunk as u64 + 1).wrapping_mul(0xd1b5_4a32_d192_ed03)
+    }
+
     pub fn num_entities(&self) -> usize {
         self.entities.len() + self.entities_gravity.len()
     }
@@ -78,43 +481,81 @@ impl World {
             2.0 * (mouse_position.x - window_size.width as f64 / 2.0),
             2.0 * (mouse_position.y - window_size.height as f64 / 2.0)
         );
-        self.settings.mouse_gravity.inner = Entity {
-            position: Point2D::new(
-                (relative_position.x * self.zoom as f64) as i32 + self.position_camera.x,
-                (-relative_position.y * self.zoom as f64) as i32 + self.position_camera.y),
-            speed: Vector2D::zero()
-        };
+        self.settings.mouse_gravity.inner.position = Point2D::new(
+            (relative_position.x * self.zoom as f64) as Coordinate + self.position_camera.x,
+            (-relative_position.y * self.zoom as f64) as Coordinate + self.position_camera.y);
         self.settings.mouse_gravity.inner.position = self.settings.mouse_gravity.inner.position.clamp(
             Point2D::zero(),
             Point2D::new(self.size_field.0, self.size_field.1)
         );
     }
 
-    pub fn apply_particles_number(&mut self) {
-        while self.num_particles > self.entities.len() as i32 {
-            self.entities.push(Entity::new_random(self.size_field));
-        }
+    pub fn new_entity(&self) -> Entity {
+        let mut entity = Entity::new_random(self.size_field, &self.rng);
+        self.settings.spawn_attributes(&mut entity, &self.rng);
+        entity
+    }
 
-        while self.num_particles < self.entities.len() as i32 {
-            self.entities.pop().unwrap();
+    // Fills up to the cap with random particles, only on an explicit request from the GUI
+    pub fn apply_particles_cap(&mut self) {
+        if self.max_particles > self.entities.len() as i32 { self.edit_entities(); }
+        while self.max_particles > self.entities.len() as i32 {
+            let entity = self.new_entity();
+            self.entities.push(entity);
         }
+        self.truncate_particles();
+    }
+
+    // The cap only removes particles, below it the count is driven by emitters, sinks and lifetimes
+    pub fn truncate_particles(&mut self) {
+        if self.max_particles >= self.entities.len() as i32 { return }
+        self.edit_entities();
+        self.entities.truncate(self.max_particles.max(0) as usize);
+        bond::truncate(&mut self.bonds, self.entities.len());
     }
 
     pub fn reset(&mut self) {
         self.entities.clear();
         self.entities_gravity.clear();
+        self.force_fields.clear();
+        self.obstacles.clear();
+        self.bonds.clear();
+        self.emitters.clear();
+        self.sinks.clear();
+        self.spawned = 0;
+        self.absorbed = 0;
+        self.expired = 0;
+        self.broken_bonds = 0;
+        self.generate();
+    }
 
-        for _ in 0..self.num_particles {
-            self.entities.push(Entity::new_random(self.size_field));
-        }
+    pub fn generate(&mut self) {
+        self.entities.clear();
+        self.bonds.clear();
+        if let Some(compute) = self.compute.as_mut() { compute.invalidate(); }
+        self.rng.seed(self.settings.seed);
+        self.step_count = 0;
+        self.num_mortal = 0;
+        self.max_rates = None;
+        self.diagnostics_history.clear();
+
+        generator::generate(self);
+    }
+
+    // Largest field scale for a window that keeps coordinate sums far from overflowing
+    pub fn max_field_scale(window_size: (Coordinate, Coordinate)) -> f32 {
+        let largest_side = window_size.0.max(window_size.1).max(1) as f64 * PRECISION as f64;
+        ((Coordinate::MAX / 4) as f64 / largest_side).min(1_000_000.0) as f32
     }
 
-    pub fn resize(&mut self, new_size: (i32, i32)) {
+    pub fn resize(&mut self, new_size: (Coordinate, Coordinate)) {
+        self.edit_entities();
         let old_size_field = self.size_field;
-        let new_size_field = (new_size.0 * PRECISION, new_size.1 * PRECISION);
+        let scale = self.settings.field_scale.clamp(1.0, World::max_field_scale(new_size)) as f64;
+        let new_size_field = (((new_size.0 * PRECISION) as f64 * scale) as Coordinate, ((new_size.1 * PRECISION) as f64 * scale) as Coordinate);
 
         self.size_field = new_size_field;
-        self.position_camera = Point2D::new(new_size.0 * PRECISION / 2, new_size.1 * PRECISION / 2);
+        self.position_camera = Point2D::new(new_size_field.0 / 2, new_size_field.1 / 2);
 
         for entity in self.entities.iter_mut() {
             entity.update_position_new_size(old_size_field, new_size_field);
@@ -126,17 +567,19 @@ impl World {
     }
 
     pub fn tilt(&mut self) {
+        self.edit_entities();
         for entity in self.entities.iter_mut() {
-            entity.position.x += fastrand::i32(-PRECISION..PRECISION);
-            entity.position.y += fastrand::i32(-PRECISION..PRECISION);
+            entity.position.x += random_coordinate(&self.rng, -PRECISION..PRECISION);
+            entity.position.y += random_coordinate(&self.rng, -PRECISION..PRECISION);
         }
         for entity in self.entities_gravity.iter_mut() {
-            entity.inner.position.x += fastrand::i32(-PRECISION..PRECISION);
-            entity.inner.position.y += fastrand::i32(-PRECISION..PRECISION);
+            entity.inner.position.x += random_coordinate(&self.rng, -PRECISION..PRECISION);
+            entity.inner.position.y += random_coordinate(&self.rng, -PRECISION..PRECISION);
         }
     }
 
     pub fn stop_speed(&mut self) {
+        self.edit_entities();
         for entity in self.entities.iter_mut() {
             entity.speed = Vector2D::zero();
         }
@@ -146,75 +589,550 @@ impl World {
     }
 
     pub fn update(&mut self) {
-        self.apply_particles_number();
+        self.truncate_particles();
         self.update_cpu();
     }
 
     pub fn update_cpu(&mut self) {
         let time = std::time::Instant::now();
-        self.elapsed_since_update = time.duration_since(self.last_update).as_secs_f32().min(0.02) * self.settings.time_factor;
-
+        let elapsed = time.duration_since(self.last_update).as_secs_f32();
         self.last_update = time;
-        
+
+        match self.settings.timestep {
+            Timestep::Variable => {
+                self.elapsed_since_update = elapsed.min(0.02) * self.settings.time_factor;
+                self.accumulator = 0.0;
+                self.interpolation = 1.0;
+                self.substeps = 1;
+                self.step();
+            }
+            Timestep::Fixed => {
+                let timestep = self.settings.fixed_timestep;
+                self.elapsed_since_update = timestep;
+                self.accumulator += elapsed * self.settings.time_factor;
+                self.substeps = 0;
+                while self.accumulator >= timestep && self.substeps < self.settings.max_substeps {
+                    self.step();
+                    self.accumulator -= timestep;
+                    self.substeps += 1;
+                }
+                // Too slow to catch up: drop the backlog instead of spiraling
+                if self.accumulator >= timestep { self.accumulator %= timestep; }
+                self.interpolation = self.accumulator / timestep;
+            }
+            Timestep::Adaptive => {
+                let mut remaining = elapsed.min(0.02) * self.settings.time_factor;
+                self.accumulator = 0.0;
+                self.interpolation = 1.0;
+                self.substeps = 0;
+                let max_steps = self.settings.max_adaptive_steps.max(1);
+                while remaining > 0.0 && self.substeps < max_steps {
+                    // Never shorter than what is needed to finish the frame within the step limit
+                    let steps_left = (max_steps - self.substeps) as f32;
+                    let timestep = self.adaptive_timestep().max(remaining / steps_left).min(remaining);
+                    self.elapsed_since_update = timestep;
+                    self.step();
+                    remaining -= timestep;
+                    self.substeps += 1;
+                }
+            }
+        }
+
+        self.benchmark_update = time.elapsed().as_secs_f32();
+    }
+
+    // Fraction of the way from the previous to the current position and the longest step a particle can travel, only for the fixed timestep
+    pub fn interpolation_parameters(&self) -> Option<(f32, f32)> {
+        if self.settings.timestep != Timestep::Fixed { return None }
+        let reach = (2.0 * self.settings.max_speed * PRECISION as f32 * self.settings.fixed_timestep).min(self.size_field.0.min(self.size_field.1) as f32 / 2.0);
+        Some((self.interpolation, reach))
+    }
+
+    pub fn interpolated_position(&self, entity: &Entity) -> Point2D<Coordinate> {
+        interpolate(entity, self.interpolation_parameters())
+    }
+
+    // Kept only for the fixed timestep, the others draw the last step as it is
+    pub fn store_previous_positions(&mut self) {
+        if self.settings.timestep != Timestep::Fixed { return }
+        for gravity in self.entities_gravity.iter_mut() { gravity.inner.previous_position = gravity.inner.position; }
+        // Particles resident on the compute backend are drawn without interpolation
+        if self.compute.as_ref().is_some_and(|compute| compute.resident()) { return }
+        self.threadpool.for_each_chunk_mut(&mut self.entities, |_chunk, _offset, entities| {
+            for entity in entities.iter_mut() { entity.previous_position = entity.position; }
+        });
+    }
+
+    pub fn gravity_cache(&self) -> Vec<(usize, GravityEntity)> {
         let mut entities_gravity_cache: Vec<(usize, GravityEntity)> = self.entities_gravity.clone().into_iter().enumerate().collect();
         if self.settings.mouse_gravity_active { entities_gravity_cache.push((usize::MAX, self.settings.mouse_gravity.clone())) }
-        
-        self.update_cpu_multithread(&entities_gravity_cache);
+        entities_gravity_cache
+    }
 
+    // Courant-like criterion on the rates of the last step: in one step nothing travels further than adaptive_length, by its speed or by its acceleration
+    // Without a measured step it returns 0, the shortest step the frame allows
+    pub fn adaptive_timestep(&self) -> f32 {
+        let (max_speed, max_acceleration) = match self.max_rates {
+            Some(rates) => { rates }
+            None => { return 0.0 }
+        };
+        let length = self.settings.adaptive_length * PRECISION as f32;
+        let mut timestep = f32::INFINITY;
+        if max_speed > 0.0 { timestep = timestep.min(length / max_speed); }
+        if max_acceleration > 0.0 { timestep = timestep.min((2.0 * length / max_acceleration).sqrt()); }
+        timestep * self.settings.adaptive_safety
+    }
+
+    pub fn step(&mut self) {
+        self.store_previous_positions();
+        let entities_gravity_cache = self.gravity_cache();
+        // Particles resident on the compute backend aren't measured, only the gravity entities
+        let mut rates = (0.0, 0.0);
+        let max = |a: (f32, f32), b: (f32, f32)| (a.0.max(b.0), a.1.max(b.1));
+
+        let gpu = self.gpu_active();
+        if gpu {
+            self.step_gpu(&entities_gravity_cache);
+        } else {
+            self.edit_entities();
+            if self.needs_grid() { self.update_grid(); }
+            if self.settings.collisions {
+                let radius = self.settings.particle_radius * PRECISION as f32;
+                let seed = self.chunk_seed(0).rotate_left(32);
+                let correction = collision::resolve(&mut self.entities, &mut self.collision_corrections, &self.grid, &self.settings, self.size_field, radius, seed, &mut self.threadpool);
+                if self.elapsed_since_update > 0.0 { rates.1 = correction / self.elapsed_since_update; }
+            }
+            self.update_interactions();
+            if !self.bonds.is_empty() {
+                let (broken, acceleration) = bond::apply(&mut self.entities, &mut self.bonds, &self.settings, self.elapsed_since_update, self.size_field, &self.rng);
+                self.broken_bonds += broken as u64;
+                rates.1 = rates.1.max(acceleration);
+            }
+            rates = max(rates, self.update_cpu_multithread(&entities_gravity_cache));
+            if !self.obstacles.is_empty() {
+                self.obstacle_broadphase.build(&self.obstacles, self.size_field);
+                obstacle::resolve(&mut self.entities, &self.obstacles, &self.obstacle_broadphase, &self.settings, &mut self.threadpool);
+            }
+            if self.settings.thermostat == Thermostat::Berendsen { self.apply_berendsen(); }
+        }
+
+        let reactions: Vec<Vector2D<f32>> = (0..self.entities_gravity.len()).map(|index| self.reaction(index)).collect();
         for (index_entity, entity) in self.entities_gravity.iter_mut().enumerate() {
             if !entity.movable { continue }
-            for (index_gravity, gravity) in entities_gravity_cache.iter() {
-                if index_entity == *index_gravity { continue }
-                entity.inner.apply_gravity(gravity, &self.settings, self.elapsed_since_update, self.size_field);
+            let acceleration = entity.inner.integrate(&entities_gravity_cache, Some(index_entity), &self.force_fields, reactions[index_entity], &self.settings, self.elapsed_since_update, self.size_field, &self.rng);
+            rates = max(rates, (entity.inner.speed.length(), acceleration));
+        }
+        self.max_rates = Some(rates);
+        for field in self.force_fields.iter_mut() {
+            field.advance(self.elapsed_since_update);
+        }
+        self.update_merging();
+        if !gpu { self.update_emitters(); }
+
+        self.step_count += 1;
+    }
+
+    // Berendsen weak coupling, the speeds relative to the center of mass are rescaled towards the target temperature
+    pub fn apply_berendsen(&mut self) {
+        let entities = &self.entities;
+        let chunks = self.threadpool.map_chunks(entities.len(), |_chunk, range| {
+            let mut sums = diagnostics::Sums::default();
+            entities[range].iter().for_each(|entity| sums.add(entity, 0.0));
+            sums
+        });
+        let mut sums = diagnostics::Sums::default();
+        chunks.iter().for_each(|chunk| sums.merge(chunk));
+        let temperature = Diagnostics::from_sums(self.step_count, &sums).temperature;
+        if temperature <= 0.0 { return }
+
+        let rate = self.elapsed_since_update * self.settings.thermostat_coupling;
+        let scale = (1.0 + rate * (self.settings.temperature / temperature - 1.0)).max(0.0).sqrt().clamp(0.8, 1.25);
+        let center = (sums.momentum / sums.mass).to_f32() * PRECISION as f32;
+        self.threadpool.for_each_chunk_mut(&mut self.entities, |_chunk, _offset, entities| {
+            for entity in entities.iter_mut() {
+                entity.speed = center + (entity.speed - center) * scale;
             }
-            entity.inner.update_position(&self.settings, self.elapsed_since_update, self.size_field);
+        });
+    }
+
+    // The compute backend only runs the gravity sources, charges, walls and boundaries
+    pub fn gpu_active(&self) -> bool {
+        self.settings.backend == Backend::Gpu && self.compute.is_some() && compute::Compute::supports(self.size_field) && self.gpu_unsupported().is_none()
+    }
+
+    // Names the first setting the compute backend can't run, the step then falls back to the cpu instead of changing the physics
+    pub fn gpu_unsupported(&self) -> Option<&'static str> {
+        let settings = &self.settings;
+        if settings.integrator != Integrator::SemiImplicitEuler { return Some("integrator") }
+        if settings.interaction != Interaction::None { return Some("interaction") }
+        if settings.collisions { return Some("collisions") }
+        if settings.thermostat != Thermostat::EnergyLoss { return Some("thermostat") }
+        if settings.coupling { return Some("coupling") }
+        if settings.accretion { return Some("accretion") }
+        if !self.force_fields.is_empty() { return Some("force fields") }
+        if !self.obstacles.is_empty() { return Some("obstacles") }
+        if !self.emitters.is_empty() || !self.sinks.is_empty() { return Some("emitters and sinks") }
+        if self.num_mortal > 0 { return Some("particle lifetimes") }
+        if !self.bonds.is_empty() { return Some("bonds") }
+        None
+    }
+
+    // Brings the particles back from the compute backend before the CPU changes them
+    pub fn edit_entities(&mut self) {
+        if let Some(compute) = self.compute.as_mut() {
+            if compute.resident() { compute.download(&mut self.entities); }
         }
+    }
 
-        self.benchmark_update = time.elapsed().as_secs_f32();
+    pub fn step_gpu(&mut self, entities_gravity_cache: &[(usize, GravityEntity)]) {
+        let num_gravities = self.entities_gravity.len();
+        let sources: Vec<compute::Source> = entities_gravity_cache.iter()
+            .map(|(index, gravity)| compute::Source::new(&soa::Source::new(*index, gravity, num_gravities, &self.settings)))
+            .collect();
+        let seed = self.chunk_seed(0);
+        let compute = self.compute.as_mut().unwrap();
+        if !compute.resident() { compute.upload(&self.entities); }
+        compute.step(&sources, &self.settings, self.size_field, self.elapsed_since_update, seed);
+        self.reaction_chunks.clear();
     }
 
-    pub fn update_cpu_singlethread(&mut self, entities_gravity_cache: &Vec<(usize, GravityEntity)>) {
-        for entity in self.entities.iter_mut() {
-            for (_index, gravity) in entities_gravity_cache.iter() {
-                entity.apply_gravity(gravity, &self.settings, self.elapsed_since_update, self.size_field);
+    pub fn update_merging(&mut self) {
+        let radius = self.settings.capture_radius * PRECISION as f32;
+
+        if self.settings.merging {
+            let mut i = 0;
+            while i < self.entities_gravity.len() {
+                let mut j = i + 1;
+                while j < self.entities_gravity.len() {
+                    let direction = Entity::direction_to(self.entities_gravity[i].inner.position, self.entities_gravity[j].inner.position, &self.settings, self.size_field, &self.rng);
+                    if direction.to_f32().length() < radius {
+                        let other = self.entities_gravity.remove(j);
+                        self.entities_gravity[i].merge(&other, direction);
+                    } else {
+                        j += 1;
+                    }
+                }
+                i += 1;
+            }
+        }
+
+        if self.settings.accretion && !self.entities_gravity.is_empty() && !self.gpu_active() {
+            let gravities = &mut self.entities_gravity;
+            let (settings, size_field, rng) = (&self.settings, self.size_field, &self.rng);
+            let mut absorbed = 0;
+            let mut kept = Vec::new();
+            let bonded = !self.bonds.is_empty();
+            self.entities.retain(|entity| {
+                let captured = gravities.iter_mut().find(|gravity| {
+                    Entity::direction_to(entity.position, gravity.inner.position, settings, size_field, rng).to_f32().square_length() < radius * radius
+                });
+                let keep = match captured {
+                    Some(gravity) => { gravity.accrete(entity, settings.accretion_force); absorbed += 1; false }
+                    None => { true }
+                };
+                if bonded { kept.push(keep); }
+                keep
+            });
+            if bonded { bond::remap(&mut self.bonds, &kept); }
+            self.absorbed += absorbed;
+        }
+    }
+
+    pub fn update_emitters(&mut self) {
+        let elapsed = self.elapsed_since_update;
+        let (sinks, settings, size_field, rng) = (&self.sinks, &self.settings, self.size_field, &self.rng);
+        let (mut absorbed, mut expired, mut mortal) = (0, 0, 0);
+        let mut kept = Vec::new();
+        let bonded = !self.bonds.is_empty();
+        self.entities.retain_mut(|entity| {
+            let mut keep = true;
+            if sinks.iter().any(|sink| sink.contains(entity.position, settings, size_field, rng)) { absorbed += 1; keep = false; }
+            else if entity.lifetime > 0.0 {
+                entity.lifetime -= elapsed;
+                if entity.lifetime <= 0.0 { expired += 1; keep = false; } else { mortal += 1; }
+            }
+            if bonded { kept.push(keep); }
+            keep
+        });
+        if bonded { bond::remap(&mut self.bonds, &kept); }
+        self.absorbed += absorbed;
+        self.expired += expired;
+
+        for emitter in self.emitters.iter_mut() {
+            let room = (self.max_particles.max(0) as usize).saturating_sub(self.entities.len());
+            let len = self.entities.len();
+            emitter.emit(elapsed, room, &self.settings, &self.rng, &mut self.entities);
+            self.spawned += (self.entities.len() - len) as u64;
+            if emitter.lifetime > 0.0 { mortal += self.entities.len() - len; }
+        }
+        self.num_mortal = mortal;
+    }
+
+    pub fn needs_grid(&self) -> bool {
+        self.debug || self.settings.collisions || self.settings.interaction.uses_grid()
+    }
+
+    pub fn neighbour_radius(&self) -> f32 {
+        let mut radius: f32 = 0.0;
+        if self.settings.collisions { radius = radius.max(self.settings.particle_radius * 2.0); }
+        match self.settings.interaction {
+            Interaction::Sph => { radius = radius.max(self.settings.sph_smoothing_length); }
+            Interaction::Boids => { radius = radius.max(self.settings.perception_radius); }
+            Interaction::ParticleLife => { radius = radius.max(self.settings.life_radius); }
+            _ => {  }
+        }
+        if radius == 0.0 { radius = self.settings.grid_cell_size; }
+        radius * PRECISION as f32
+    }
+
+    pub fn update_grid(&mut self) {
+        let cell_size = self.neighbour_radius().ceil() as Coordinate;
+        let wrap = (self.settings.boundary_x == Boundary::Wrap, self.settings.boundary_y == Boundary::Wrap);
+        self.grid.build(&self.entities, self.size_field, cell_size, wrap, &mut self.threadpool);
+    }
+
+    pub fn update_interactions(&mut self) {
+        match self.settings.interaction {
+            Interaction::None => {
+                self.accelerations.clear();
+            }
+            Interaction::BarnesHut => {
+                let wrap = (self.settings.boundary_x == Boundary::Wrap, self.settings.boundary_y == Boundary::Wrap);
+                self.quadtree.build(&self.entities, self.size_field, wrap);
+                self.accelerations.resize(self.entities.len(), Vector2D::zero());
+
+                let quadtree = &self.quadtree;
+                let theta = self.settings.theta;
+                let softening = self.settings.softening * PRECISION as f32;
+                let strength = self.settings.nbody_gravity * (PRECISION as f32).powi(3);
+                self.threadpool.for_each_chunk_mut(&mut self.accelerations, |_chunk, offset, accelerations| {
+                    for (i, acceleration) in accelerations.iter_mut().enumerate() {
+                        *acceleration = quadtree.acceleration(offset + i, theta, softening) * strength;
+                    }
+                });
+            }
+            Interaction::Sph => {
+                sph::update_densities(&self.entities, &self.grid, &self.settings, &mut self.densities, &mut self.threadpool);
+                sph::update_accelerations(&self.entities, &self.grid, &self.settings, &self.densities, &mut self.accelerations, &mut self.threadpool);
+            }
+            Interaction::Boids => {
+                boids::update_accelerations(&self.entities, &self.grid, &self.settings, &mut self.accelerations, &mut self.threadpool);
+            }
+            Interaction::ParticleLife => {
+                particle_life::update_accelerations(&self.entities, &self.grid, &self.settings, &mut self.accelerations, &mut self.threadpool);
             }
-            entity.update_position(&self.settings, self.elapsed_since_update, self.size_field);
         }
     }
 
-    pub fn update_cpu_multithread(&mut self, entities_gravity_cache: &Vec<(usize, GravityEntity)>) {
+    pub fn update_cpu_singlethread(&mut self, entities_gravity_cache: &[(usize, GravityEntity)]) {
+        let rng = fastrand::Rng::with_seed(self.chunk_seed(0));
+        for (i, entity) in self.entities.iter_mut().enumerate() {
+            let extra_acceleration = self.accelerations.get(i).copied().unwrap_or(Vector2D::zero());
+            entity.integrate(entities_gravity_cache, None, &self.force_fields, extra_acceleration, &self.settings, self.elapsed_since_update, self.size_field, &rng);
+        }
+    }
+
+    // Returns the largest speed and acceleration of the particles
+    pub fn update_cpu_multithread(&mut self, entities_gravity_cache: &[(usize, GravityEntity)]) -> (f32, f32) {
         let len = self.entities.len();
         let num_threads = self.threadpool.num_threads();
         let size_chunk = len / num_threads + 1;
+        let coupling = self.settings.coupling;
+        let num_gravities = self.entities_gravity.len();
+        let diagnostics = self.settings.diagnostics;
+        let simd = self.settings.simd;
+        let sources: Vec<soa::Source> = if simd {
+            entities_gravity_cache.iter().map(|(index, gravity)| soa::Source::new(*index, gravity, num_gravities, &self.settings)).collect()
+        } else {
+            Vec::new()
+        };
+        let sources = &sources;
+
+        self.soa_chunks.resize(num_threads, soa::Particles::default());
+        self.diagnostic_chunks.clear();
+        self.diagnostic_chunks.resize(num_threads, diagnostics::Sums::default());
+        self.rate_chunks.clear();
+        self.rate_chunks.resize(num_threads, (0.0, 0.0));
+        self.reaction_chunks.resize(num_threads, Vec::new());
+        for reactions in self.reaction_chunks.iter_mut() {
+            reactions.clear();
+            reactions.resize(entities_gravity_cache.len(), Vector2D::zero());
+        }
 
         for thread in 0..num_threads {
             let entities = std::sync::atomic::AtomicPtr::new(&mut self.entities);
+            let reactions = std::sync::atomic::AtomicPtr::new(&mut self.reaction_chunks[thread]);
+            let sums = std::sync::atomic::AtomicPtr::new(&mut self.diagnostic_chunks[thread]);
+            let rates = std::sync::atomic::AtomicPtr::new(&mut self.rate_chunks[thread]);
+            let particles = std::sync::atomic::AtomicPtr::new(&mut self.soa_chunks[thread]);
             let world = std::sync::atomic::AtomicPtr::new(self);
             let range = (thread * size_chunk)..((thread + 1) * size_chunk);
             let (range_begin, range_end) = (range.start, range.end);
+            let seed = self.chunk_seed(thread);
 
             unsafe { self.threadpool.send_work_unsafe(move || {
                 let entities = entities.load(std::sync::atomic::Ordering::Relaxed);
                 let entities = &mut *entities;
+                let reactions = reactions.load(std::sync::atomic::Ordering::Relaxed);
+                let reactions = &mut *reactions;
+                let sums = sums.load(std::sync::atomic::Ordering::Relaxed);
+                let sums = &mut *sums;
+                let rates = rates.load(std::sync::atomic::Ordering::Relaxed);
+                let rates = &mut *rates;
                 let world = world.load(std::sync::atomic::Ordering::Relaxed);
                 let world = &mut *world;
+                let rng = fastrand::Rng::with_seed(seed);
+                if simd {
+                    let particles = particles.load(std::sync::atomic::Ordering::Relaxed);
+                    let particles = &mut *particles;
+                    let range = range_begin.min(entities.len())..range_end.min(entities.len());
+                    let extra_accelerations = world.accelerations.get(range.clone()).unwrap_or(&[]);
+                    let entities = &mut entities[range];
+                    particles.gather(entities, extra_accelerations);
+                    particles.integrate(entities, sources, coupling.then_some(reactions.as_mut_slice()), &world.force_fields, &world.settings, world.elapsed_since_update, world.size_field, &rng);
+                    *rates = (entities.iter().map(|entity| entity.speed.length()).fold(0.0, f32::max), particles.max_acceleration(world.elapsed_since_update));
+                    if diagnostics {
+                        let potentials = particles.potential_energies(entities, sources, &world.settings, world.size_field, &rng);
+                        entities.iter().zip(potentials.iter()).for_each(|(entity, potential)| sums.add(entity, *potential));
+                    }
+                    return
+                }
                 for i in range_begin..range_end {
                     if let Some(entity) = entities.get_mut(i) {
-                        for (_index, gravity) in entities_gravity_cache.iter() {
-                            entity.apply_gravity(gravity, &world.settings, world.elapsed_since_update, world.size_field);
+                        if coupling {
+                            let charge_to_mass = entity.charge_to_mass();
+                            for (reaction, (index, gravity)) in reactions.iter_mut().zip(entities_gravity_cache.iter()) {
+                                if *index >= num_gravities || !gravity.movable { continue }
+                                let acceleration = Entity::gravity_acceleration(entity.position, gravity, &world.settings, world.size_field, &rng)
+                                    + Entity::charge_acceleration(entity.position, charge_to_mass, &gravity.inner, &world.settings, world.size_field, &rng);
+                                *reaction -= acceleration * entity.mass;
+                            }
+                        }
+                        let extra_acceleration = world.accelerations.get(i).copied().unwrap_or(Vector2D::zero());
+                        let acceleration = entity.integrate(entities_gravity_cache, None, &world.force_fields, extra_acceleration, &world.settings, world.elapsed_since_update, world.size_field, &rng);
+                        *rates = (rates.0.max(entity.speed.length()), rates.1.max(acceleration));
+                        if diagnostics {
+                            sums.add(entity, entity.potential_energy(entities_gravity_cache, &world.settings, world.size_field, &rng));
                         }
-                        entity.update_position(&world.settings, world.elapsed_since_update, world.size_field);
                     }
                 }
             })};
         }
         self.threadpool.wait();
+
+        if diagnostics { self.update_diagnostics(); }
+        self.rate_chunks.iter().fold((0.0, 0.0), |a, b| (a.0.max(b.0), a.1.max(b.1)))
+    }
+
+    pub fn update_diagnostics(&mut self) {
+        let mut sums = diagnostics::Sums::default();
+        self.diagnostic_chunks.iter().for_each(|chunk| sums.merge(chunk));
+        self.diagnostics = Diagnostics::from_sums(self.step_count, &sums);
+        self.diagnostics_history.push_back(self.diagnostics);
+        while self.diagnostics_history.len() > self.settings.diagnostics_history as usize {
+            self.diagnostics_history.pop_front();
+        }
+    }
+
+    // Sum of the per chunk back reactions of the particles on each gravity entity, scaled by the mass ratio
+    pub fn reaction(&self, index: usize) -> Vector2D<f32> {
+        if !self.settings.coupling { return Vector2D::zero() }
+        let total: Vector2D<f32> = self.reaction_chunks.iter().filter_map(|reactions| reactions.get(index)).copied().sum();
+        total * self.settings.coupling_mass_ratio
+    }
+
+    pub fn update_vertices(&mut self) {
+        let camera = self.position_camera;
+        if let Some(compute) = self.compute.as_mut().filter(|compute| compute.resident()) {
+            compute.update_vertices(camera, &self.settings, self.size_field);
+            self.vertices.clear();
+            return
+        }
+        let interpolation = self.interpolation_parameters();
+        let entities = &self.entities;
+        self.vertices.resize(entities.len(), Default::default());
+        self.threadpool.for_each_chunk_mut(&mut self.vertices, |_chunk, offset, vertices| {
+            for (i, vertex) in vertices.iter_mut().enumerate() {
+                let entity = &entities[offset + i];
+                *vertex = renderer::ParticleVertex::new(entity, interpolate(entity, interpolation), camera);
+            }
+        });
     }
 
     pub fn draw(&mut self, renderer: &mut renderer::Renderer) {
         let time = std::time::Instant::now();
+        self.update_vertices();
         renderer.draw(self);
 
         self.benchmark_draw = time.elapsed().as_secs_f32();
     }
+}
+
+// Drawn state between the last two steps, a jump longer than a step can travel (wrap, respawn, new particle) is drawn where it landed
+fn interpolate(entity: &Entity, interpolation: Option<(f32, f32)>) -> Point2D<Coordinate> {
+    let (fraction, reach) = match interpolation {
+        Some(interpolation) => { interpolation }
+        None => { return entity.position }
+    };
+    let step = (entity.position - entity.previous_position).to_f32();
+    if step.x.abs() > reach || step.y.abs() > reach { return entity.position }
+    entity.previous_position + (step * fraction).round().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Positions and speeds after a few steps with every source of randomness switched on
+    fn run(seed: u64, threads: usize) -> Vec<(Point2D<Coordinate>, Vector2D<f32>)> {
+        let mut world = crate::new_world();
+        while world.threadpool.num_threads() < threads { world.threadpool.new_thread(); }
+        world.max_particles = 1_024;
+        world.settings.seed = seed;
+        world.settings.thermostat = Thermostat::Brownian;
+        world.settings.collisions = true;
+        world.elapsed_since_update = 1.0 / 120.0;
+        world.reset();
+        for _ in 0..5 { world.step(); }
+        world.entities.iter().map(|entity| (entity.position, entity.speed)).collect()
+    }
+
+    // The gravity entity weighs 1 / coupling_mass_ratio, it and the particles exchange equal and opposite impulses
+    fn total_momentum(world: &World) -> Vector2D<f64> {
+        let particles: Vector2D<f64> = world.entities.iter().map(|entity| entity.speed.to_f64() * entity.mass as f64).sum();
+        let gravity = world.entities_gravity[0].inner.speed.to_f64() / world.settings.coupling_mass_ratio as f64;
+        particles + gravity
+    }
+
+    #[test]
+    fn coupling_conserves_momentum() {
+        for simd in [false, true] {
+            let mut world = crate::new_world();
+            world.max_particles = 2_000;
+            world.settings.energy_loss = 0.0;
+            world.settings.coupling = true;
+            world.settings.coupling_mass_ratio = 0.001;
+            world.settings.simd = simd;
+            world.elapsed_since_update = 1.0 / 120.0;
+            world.reset();
+            let mut gravity = GravityEntity::default();
+            gravity.inner.position = Point2D::new(world.size_field.0 / 2, world.size_field.1 / 2);
+            gravity.gravity.compute = GravityCompute::Softened { force: 100.0, softening: 5.0 };
+            gravity.movable = true;
+            world.entities_gravity.push(gravity);
+
+            let initial = total_momentum(&world);
+            for _ in 0..10 { world.step(); }
+            let exchanged: f64 = world.entities.iter().map(|entity| entity.speed.to_f64().length() * entity.mass as f64).sum();
+            let drift = (total_momentum(&world) - initial).length();
+            assert!(exchanged > 0.0 && drift < exchanged * 1e-4, "simd {} drift {} exchanged {}", simd, drift, exchanged);
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let first = run(42, 4);
+        assert_eq!(first, run(42, 4));
+        assert_ne!(first, run(43, 4));
+    }
 }
\ No newline at end of file